@@ -0,0 +1,17 @@
+use std::{env, process::Command};
+
+fn main() {
+    // `routes::health` embeds the migrations to check for pending ones
+    println!("cargo:rerun-if-changed=../migrations");
+
+    // Reported by `/metrics` as `fantasia_build_info`
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=FANTASIA_RUSTC_VERSION={version}");
+}
@@ -1,4 +1,30 @@
+pub mod activation;
+pub mod addr;
+mod compression;
+pub mod cors;
 pub mod fantasia;
+pub mod http;
+pub mod limits;
+pub mod listener;
+pub mod panic;
+pub mod proxy;
+pub mod rate_limit;
+mod request_metrics;
 pub mod router;
+pub mod security_headers;
+mod server;
+pub mod shutdown;
+pub mod tls;
+pub mod trace;
+pub mod upgrade;
 
+pub use activation::InheritedSocket;
+pub use addr::{ListenAddr, PeerAddr, UnixSocket};
+pub use cors::CorsSettings;
 pub use fantasia::{Fantasia, FantasiaBuilder};
+pub use http::HttpSettings;
+pub use limits::ReloadableLimits;
+pub use listener::{ListenerSettings, Protocol, Role};
+pub use shutdown::Shutdown;
+pub use tls::{ReloadableCert, TlsConnection, TlsSettings, TlsVersion};
+pub use upgrade::ListenerFd;
@@ -0,0 +1,234 @@
+//! Listening sockets inherited from systemd socket activation or a parent process.
+//!
+//! See `sd_listen_fds(3)` for the protocol. Sockets are passed as consecutive file descriptors
+//! starting at 3 along with these variables:
+//!
+//! | Variables        | Description                                       |
+//! | ---              | ---                                               |
+//! | `LISTEN_PID`     | PID of the process that should use the sockets    |
+//! | `LISTEN_FDS`     | Number of passed file descriptors                 |
+//! | `LISTEN_FDNAMES` | Optional colon separated names of each descriptor |
+//!
+//! A running Fantasia process can't know the PID of the new process it hands its listeners to
+//! during an upgrade. It sets `FANTASIA_UPGRADE_PID` to its own PID instead of `LISTEN_PID` so
+//! that only its direct child uses the sockets.
+
+use std::{
+    env, io,
+    net::TcpListener,
+    os::{
+        fd::{BorrowedFd, FromRawFd, RawFd},
+        unix::{self, net::UnixListener},
+    },
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use nix::{
+    fcntl::{fcntl, FcntlArg, FdFlag},
+    sys::socket::{
+        getsockname, getsockopt, sockopt, AddressFamily, SockType, SockaddrLike, SockaddrStorage,
+    },
+};
+use tracing::{debug, info};
+
+use super::addr::ListenAddr;
+
+/// First file descriptor passed by systemd.
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// Whether [listen_fds] has taken ownership of the passed descriptors.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// PID of the process that handed its listeners over to this one during an upgrade.
+pub const UPGRADE_PID_VAR: &str = "FANTASIA_UPGRADE_PID";
+
+/// Listening socket that Fantasia didn't bind itself.
+#[derive(Debug)]
+pub enum InheritedSocket {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// Socket passed via `LISTEN_FDS` along with its name from `LISTEN_FDNAMES`.
+#[derive(Debug)]
+pub struct ActivatedSocket {
+    pub socket: InheritedSocket,
+    pub name: Option<String>,
+}
+
+impl InheritedSocket {
+    /// Take ownership of the listening stream socket `fd`.
+    ///
+    /// The descriptor is marked close-on-exec so that it isn't leaked to child processes.
+    ///
+    /// # Safety
+    /// `fd` must be an open file descriptor that isn't owned by anything else. The descriptor is
+    /// left open if an error is returned.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> io::Result<InheritedSocket> {
+        let borrowed = BorrowedFd::borrow_raw(fd);
+
+        if getsockopt(&borrowed, sockopt::SockType)? != SockType::Stream
+            || !getsockopt(&borrowed, sockopt::AcceptConn)?
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("File descriptor {fd} is not a listening stream socket"),
+            ));
+        }
+        fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+
+        match getsockname::<SockaddrStorage>(fd)?.family() {
+            Some(AddressFamily::Inet | AddressFamily::Inet6) => {
+                Ok(InheritedSocket::Tcp(TcpListener::from_raw_fd(fd)))
+            }
+            Some(AddressFamily::Unix) => Ok(InheritedSocket::Unix(UnixListener::from_raw_fd(fd))),
+            family => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("File descriptor {fd} has an unsupported address family: {family:?}"),
+            )),
+        }
+    }
+
+    /// Local address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<ListenAddr> {
+        match self {
+            InheritedSocket::Tcp(listener) => listener.local_addr().map(ListenAddr::Tcp),
+            // Abstract and unnamed sockets don't have a path
+            InheritedSocket::Unix(listener) => Ok(ListenAddr::Unix(
+                listener
+                    .local_addr()?
+                    .as_pathname()
+                    .map(ToOwned::to_owned)
+                    .unwrap_or_default(),
+            )),
+        }
+    }
+}
+
+/// Sockets passed to this process by systemd or by a parent Fantasia process during an upgrade.
+///
+/// Returns an empty [Vec] if the variables are unset or meant for another process, or if the
+/// sockets were already taken by an earlier call.
+///
+/// The variables are left in the environment because other threads may be reading it. Processes
+/// started for an upgrade get their own variables instead of inheriting these.
+#[tracing::instrument]
+pub fn listen_fds() -> io::Result<Vec<ActivatedSocket>> {
+    // The descriptors can only be owned once
+    if TAKEN.swap(true, Ordering::AcqRel) {
+        return Ok(Vec::new());
+    }
+
+    let (listen_pid, pid) = match env::var("LISTEN_PID") {
+        Ok(listen_pid) => (Some(listen_pid), process::id()),
+        Err(_) => (env::var(UPGRADE_PID_VAR).ok(), unix::process::parent_id()),
+    };
+    let listen_fds = env::var("LISTEN_FDS").ok();
+    let listen_fdnames = env::var("LISTEN_FDNAMES").ok();
+
+    let fds = parse_env(
+        listen_pid.as_deref(),
+        listen_fds.as_deref(),
+        listen_fdnames.as_deref(),
+        pid,
+    )?;
+    info!("Inherited {} sockets", fds.len());
+
+    fds.into_iter()
+        .map(|(fd, name)| {
+            debug!("Inherited file descriptor {fd} ({name:?})");
+            // SAFETY: systemd or the parent passes ownership of these descriptors to this process.
+            let socket = unsafe { InheritedSocket::from_raw_fd(fd)? };
+            Ok(ActivatedSocket { socket, name })
+        })
+        .collect()
+}
+
+// Pair each passed file descriptor with its name
+fn parse_env(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    listen_fdnames: Option<&str>,
+    pid: u32,
+) -> io::Result<Vec<(RawFd, Option<String>)>> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(Vec::new());
+    };
+
+    let invalid = |var| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("`{var}` is not a valid number"),
+        )
+    };
+    let listen_pid: u32 = listen_pid.parse().map_err(|_| invalid("LISTEN_PID"))?;
+    let listen_fds: RawFd = listen_fds.parse().map_err(|_| invalid("LISTEN_FDS"))?;
+
+    // The variables were meant for a parent process
+    if listen_pid != pid {
+        return Ok(Vec::new());
+    }
+
+    // A garbage count could overflow the descriptor range
+    let end = LISTEN_FDS_START
+        .checked_add(listen_fds)
+        .filter(|_| listen_fds >= 0)
+        .ok_or_else(|| invalid("LISTEN_FDS"))?;
+
+    let mut names = listen_fdnames
+        .into_iter()
+        .flat_map(|names| names.split(':'));
+    Ok((LISTEN_FDS_START..end)
+        .map(|fd| {
+            // Fantasia leaves unnamed sockets empty when handing them over
+            let name = names.next().filter(|name| !name.is_empty());
+            (fd, name.map(ToOwned::to_owned))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_env;
+
+    #[test]
+    fn missing_env_yields_no_fds() {
+        assert!(parse_env(None, None, None, 42).unwrap().is_empty());
+        assert!(parse_env(Some("42"), None, None, 42).unwrap().is_empty());
+    }
+
+    #[test]
+    fn other_pid_yields_no_fds() {
+        assert!(parse_env(Some("7"), Some("2"), None, 42)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn fds_are_paired_with_names() {
+        assert_eq!(
+            vec![
+                (3, Some("https".to_string())),
+                (4, Some("admin".to_string())),
+                (5, None)
+            ],
+            parse_env(Some("42"), Some("3"), Some("https:admin"), 42).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_names_are_unnamed() {
+        assert_eq!(
+            vec![(3, None), (4, Some("admin".to_string()))],
+            parse_env(Some("42"), Some("2"), Some(":admin"), 42).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_count_fails() {
+        assert!(parse_env(Some("42"), Some("many"), None, 42).is_err());
+        assert!(parse_env(Some("42"), Some("-1"), None, 42).is_err());
+        assert!(parse_env(Some("42"), Some(&i32::MAX.to_string()), None, 42).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Addresses for TCP and Unix domain socket listeners.
+
+use std::{
+    fmt::{self, Display},
+    fs::{self, DirBuilder, Permissions},
+    io,
+    net::SocketAddr,
+    os::unix::{
+        self,
+        fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use tokio::net::UnixListener;
+use tracing::{debug, info, warn};
+
+/// Local address of a bound [super::Fantasia] instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+/// Address of the peer on the other end of a connection.
+///
+/// Fantasia attaches this to every request as [axum::extract::ConnectInfo] regardless of the
+/// listener's transport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerAddr {
+    Tcp(SocketAddr),
+    /// Unix socket clients are usually unnamed.
+    Unix(Option<PathBuf>),
+}
+
+/// Unix domain socket to bind along with its permissions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixSocket {
+    pub path: PathBuf,
+    /// File mode of the socket (e.g. `0o660`).
+    pub mode: Option<u32>,
+    /// Owning user ID of the socket.
+    pub uid: Option<u32>,
+    /// Owning group ID of the socket.
+    pub gid: Option<u32>,
+}
+
+/// Removes a bound Unix socket from the file system when dropped.
+///
+/// The socket is left in place if it was handed over to another process.
+pub(crate) struct UnixSocketGuard {
+    path: PathBuf,
+    handed_over: Arc<AtomicBool>,
+}
+
+impl UnixSocket {
+    /// Bind a listener at `path` and apply the socket's mode and owner.
+    ///
+    /// A stale socket left behind by a previous run is replaced. A socket that still accepts
+    /// connections is left alone and [io::ErrorKind::AddrInUse] is returned instead.
+    pub(crate) fn bind(&self) -> io::Result<(UnixListener, UnixSocketGuard)> {
+        remove_stale_socket(&self.path)?;
+
+        // The socket is bound in a private directory and linked into place once its mode and owner
+        // are set so that clients never see it with the process' umask
+        let staging = staging_dir(&self.path)?;
+        let staged = staging.join("s");
+        let bound = UnixListener::bind(&staged).and_then(|listener| {
+            if let Some(mode) = self.mode {
+                fs::set_permissions(&staged, Permissions::from_mode(mode))?;
+            }
+            if self.uid.is_some() || self.gid.is_some() {
+                unix::fs::chown(&staged, self.uid, self.gid)?;
+            }
+            // Unlike `rename`, `link` fails rather than replacing a socket that another process
+            // bound at the path since the stale one was removed
+            fs::hard_link(&staged, &self.path).map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("`{}` was taken by another process", self.path.display()),
+                ),
+                _ => e,
+            })?;
+            Ok(listener)
+        });
+        let _ = fs::remove_dir_all(&staging);
+        let listener = bound?;
+
+        let guard = UnixSocketGuard {
+            path: self.path.clone(),
+            handed_over: Arc::default(),
+        };
+
+        info!("Bound Unix socket: {}", self.path.display());
+        Ok((listener, guard))
+    }
+}
+
+// Directory next to `path` that only this process may enter. It's on the same file system, so the
+// socket can be linked out of it. The name is short because socket paths are limited to about
+// 100 bytes.
+fn staging_dir(path: &Path) -> io::Result<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let staging = parent.join(format!(".fantasia-{}", std::process::id()));
+
+    // Left behind by a crashed process that had the same PID
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    DirBuilder::new().mode(0o700).create(&staging)?;
+
+    Ok(staging)
+}
+
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => match unix::net::UnixStream::connect(path) {
+            Ok(_) => Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("`{}` is in use by another process", path.display()),
+            )),
+            Err(_) => {
+                debug!("Removing stale Unix socket: {}", path.display());
+                fs::remove_file(path)
+            }
+        },
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("`{}` exists and is not a socket", path.display()),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+impl UnixSocketGuard {
+    /// Flag that keeps the socket file once set.
+    pub(crate) fn handed_over(&self) -> Arc<AtomicBool> {
+        self.handed_over.clone()
+    }
+}
+
+impl Drop for UnixSocketGuard {
+    fn drop(&mut self) {
+        if self.handed_over.load(Ordering::SeqCst) {
+            debug!(
+                "Leaving Unix socket for the new process: {}",
+                self.path.display()
+            );
+        } else if let Err(e) = fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove Unix socket `{}`: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+impl Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "{addr}"),
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl Display for PeerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerAddr::Tcp(addr) => write!(f, "{addr}"),
+            PeerAddr::Unix(Some(path)) => write!(f, "unix:{}", path.display()),
+            PeerAddr::Unix(None) => write!(f, "unix:(unnamed)"),
+        }
+    }
+}
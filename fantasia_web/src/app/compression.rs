@@ -0,0 +1,177 @@
+//! Response compression with a level per algorithm.
+//!
+//! A [CompressionLayer] compresses every algorithm at the same level, so each enabled algorithm
+//! gets a layer of its own. [choose_encoding] picks the client's preferred algorithm before the
+//! handler runs, and only the layer for that algorithm compresses the response.
+
+use axum::{
+    body::HttpBody,
+    extract::{Request, State},
+    http::{header, HeaderMap, Response},
+    middleware::Next,
+    response::Response as AxumResponse,
+};
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+use super::http::CompressionSettings;
+
+/// Compression algorithms in order of preference when the client accepts several equally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Encoding {
+    Deflate,
+    Gzip,
+    Br,
+}
+
+/// Response extension with the algorithm chosen for the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Chosen(Encoding);
+
+/// Compresses responses whose chosen algorithm is `encoding`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CompressWhen {
+    encoding: Encoding,
+}
+
+impl CompressionSettings {
+    fn enabled(&self, encoding: Encoding) -> bool {
+        match encoding {
+            Encoding::Deflate => self.deflate,
+            Encoding::Gzip => self.gzip,
+            Encoding::Br => self.br,
+        }
+    }
+
+    /// Layer that compresses responses with `encoding` at its level.
+    pub(crate) fn layer(&self, encoding: Encoding) -> CompressionLayer<CompressWhen> {
+        let level = match encoding {
+            Encoding::Deflate => self.deflate_level,
+            Encoding::Gzip => self.gzip_level,
+            Encoding::Br => self.br_level,
+        };
+
+        CompressionLayer::new()
+            .gzip(encoding == Encoding::Gzip && self.gzip)
+            .deflate(encoding == Encoding::Deflate && self.deflate)
+            .br(encoding == Encoding::Br && self.br)
+            .quality(level.into())
+            .compress_when(CompressWhen { encoding })
+    }
+}
+
+impl Predicate for CompressWhen {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        response.extensions().get() == Some(&Chosen(self.encoding))
+    }
+}
+
+/// Mark the response with the enabled algorithm that the client prefers if it should be
+/// compressed.
+///
+/// The size is checked here because the layers' bodies don't report the size of the body they
+/// wrap to the layers outside them.
+pub(crate) async fn choose_encoding(
+    State(settings): State<CompressionSettings>,
+    request: Request,
+    next: Next,
+) -> AxumResponse {
+    let encoding = preferred_encoding(request.headers(), &settings);
+
+    let mut response = next.run(request).await;
+    // Same as tower-http's default predicate with a configurable size
+    let compress = SizeAbove::new(settings.min_size)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::SSE);
+    if let Some(encoding) = encoding.filter(|_| compress.should_compress(&response)) {
+        response.extensions_mut().insert(Chosen(encoding));
+    }
+    response
+}
+
+// Highest q-value wins, with ties going to the better algorithm like tower-http
+fn preferred_encoding(headers: &HeaderMap, settings: &CompressionSettings) -> Option<Encoding> {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|coding| {
+            let mut params = coding.split(';');
+            let encoding = match params.next()?.trim().to_ascii_lowercase().as_str() {
+                "gzip" | "x-gzip" => Encoding::Gzip,
+                "deflate" => Encoding::Deflate,
+                "br" => Encoding::Br,
+                _ => return None,
+            };
+            let qvalue = match params.next() {
+                Some(param) => qvalue(param)?,
+                None => 1000,
+            };
+
+            Some((encoding, qvalue))
+        })
+        .filter(|&(encoding, qvalue)| qvalue > 0 && settings.enabled(encoding))
+        .max_by_key(|&(encoding, qvalue)| (qvalue, encoding))
+        .map(|(encoding, _)| encoding)
+}
+
+// `q=0.5` in thousandths, or `None` if it's invalid
+fn qvalue(param: &str) -> Option<u16> {
+    let (name, value) = param.trim().split_once('=')?;
+    if !name.eq_ignore_ascii_case("q") {
+        return None;
+    }
+
+    let value: f32 = value.parse().ok()?;
+    (0.0..=1.0)
+        .contains(&value)
+        .then(|| (value * 1000.0).round() as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    use super::{preferred_encoding, Encoding};
+    use crate::app::http::CompressionSettings;
+
+    fn preferred(accept: &'static str, settings: &CompressionSettings) -> Option<Encoding> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static(accept));
+        preferred_encoding(&headers, settings)
+    }
+
+    #[test]
+    fn clients_preferences_win() {
+        let settings = CompressionSettings::default();
+
+        assert_eq!(
+            Some(Encoding::Br),
+            preferred("gzip, deflate, br", &settings)
+        );
+        assert_eq!(Some(Encoding::Gzip), preferred("br;q=0.5, gzip", &settings));
+        assert_eq!(
+            Some(Encoding::Deflate),
+            preferred("deflate, br;q=0", &settings)
+        );
+        assert_eq!(None, preferred("identity, zstd", &settings));
+        assert_eq!(None, preferred("gzip;q=2", &settings));
+    }
+
+    #[test]
+    fn disabled_algorithms_are_skipped() {
+        let settings = CompressionSettings {
+            br: false,
+            ..Default::default()
+        };
+
+        assert_eq!(Some(Encoding::Gzip), preferred("br, gzip;q=0.1", &settings));
+    }
+}
@@ -0,0 +1,252 @@
+//! Cross-origin resource sharing for browsers on other origins, e.g. `[fantasia.cors]`.
+//!
+//! Requests from allowed origins get `Access-Control-Allow-*` headers, and preflight requests are
+//! answered before they reach the routes. Origins that aren't allowed get no CORS headers at all,
+//! which browsers treat as a rejection.
+
+use std::{fmt, time::Duration};
+
+use axum::http::{request::Parts, HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Deserializer};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use super::http::deserialize_seconds;
+
+/// CORS policy for public routes.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CorsSettings {
+    /// Origins allowed to make cross-origin requests.
+    pub allowed_origins: Vec<AllowedOrigin>,
+    /// Methods allowed in cross-origin requests. Defaults to `GET`, `HEAD`, and `POST`.
+    #[serde(deserialize_with = "deserialize_methods", default = "default_methods")]
+    pub allowed_methods: Vec<Method>,
+    /// Request headers allowed in cross-origin requests besides the CORS-safelisted ones.
+    #[serde(deserialize_with = "deserialize_headers", default)]
+    pub allowed_headers: Vec<HeaderName>,
+    /// Whether browsers may send cookies and `Authorization` with cross-origin requests.
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// Time browsers may cache preflight responses. Zero leaves it to the browser.
+    #[serde(
+        deserialize_with = "deserialize_seconds",
+        alias = "max_age_seconds",
+        default
+    )]
+    pub max_age: Duration,
+}
+
+/// Origin allowed by [CorsSettings].
+///
+/// Either an exact origin such as `https://fantasia.example` or every subdomain of an origin such
+/// as `https://*.fantasia.example`. Wildcards don't match the origin itself.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum AllowedOrigin {
+    Exact(HeaderValue),
+    Subdomains {
+        /// Scheme including `://`.
+        scheme: String,
+        /// Host after the wildcard including the leading `.` and the port, if any.
+        suffix: String,
+    },
+}
+
+impl CorsSettings {
+    /// Allow `origins` with the default methods and headers.
+    pub fn new(origins: Vec<AllowedOrigin>) -> Self {
+        CorsSettings {
+            allowed_origins: origins,
+            allowed_methods: default_methods(),
+            allowed_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn layer(&self) -> CorsLayer {
+        let origins = self.allowed_origins.clone();
+        let layer = CorsLayer::new()
+            .allow_origin(AllowOrigin::predicate(
+                move |origin: &HeaderValue, _: &Parts| {
+                    origins.iter().any(|allowed| allowed.matches(origin))
+                },
+            ))
+            .allow_methods(self.allowed_methods.clone())
+            .allow_headers(self.allowed_headers.clone())
+            .allow_credentials(self.allow_credentials);
+
+        if self.max_age.is_zero() {
+            layer
+        } else {
+            layer.max_age(self.max_age)
+        }
+    }
+}
+
+impl AllowedOrigin {
+    /// Whether a request's `Origin` header is allowed.
+    pub fn matches(&self, origin: &HeaderValue) -> bool {
+        match self {
+            AllowedOrigin::Exact(allowed) => allowed == origin,
+            AllowedOrigin::Subdomains { scheme, suffix } => {
+                let Some(subdomain) = origin
+                    .to_str()
+                    .ok()
+                    .and_then(|origin| origin.strip_prefix(scheme.as_str()))
+                    .and_then(|host| host.strip_suffix(suffix.as_str()))
+                else {
+                    return false;
+                };
+
+                !subdomain.is_empty()
+                    && subdomain
+                        .split('.')
+                        .all(|label| !label.is_empty() && is_label(label))
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for AllowedOrigin {
+    type Error = String;
+
+    fn try_from(origin: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid CORS origin `{origin}`");
+
+        // Origins are serialized as `scheme://host[:port]` without a path or trailing slash
+        let (scheme, host) = origin.split_once("://").ok_or_else(invalid)?;
+        if scheme.is_empty() || host.is_empty() || host.contains(['/', '?', '#', '@']) {
+            return Err(invalid());
+        }
+
+        match host.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') && !suffix.contains('*') => {
+                Ok(AllowedOrigin::Subdomains {
+                    scheme: format!("{scheme}://"),
+                    suffix: suffix.to_owned(),
+                })
+            }
+            Some(_) => Err(invalid()),
+            None if host.contains('*') => Err(invalid()),
+            None => HeaderValue::try_from(origin.as_str())
+                .map(AllowedOrigin::Exact)
+                .map_err(|_| invalid()),
+        }
+    }
+}
+
+impl fmt::Display for AllowedOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllowedOrigin::Exact(origin) => write!(f, "{}", origin.to_str().unwrap_or_default()),
+            AllowedOrigin::Subdomains { scheme, suffix } => write!(f, "{scheme}*{suffix}"),
+        }
+    }
+}
+
+// DNS label characters. Anything else, such as `/` or `:`, means the origin only looks similar.
+fn is_label(label: &str) -> bool {
+    label
+        .bytes()
+        .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+}
+
+fn default_methods() -> Vec<Method> {
+    vec![Method::GET, Method::HEAD, Method::POST]
+}
+
+fn deserialize_methods<'de, D>(deserializer: D) -> Result<Vec<Method>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|method| {
+            Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                .map_err(|_| serde::de::Error::custom(format!("Invalid HTTP method `{method}`")))
+        })
+        .collect()
+}
+
+fn deserialize_headers<'de, D>(deserializer: D) -> Result<Vec<HeaderName>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| {
+            HeaderName::try_from(name.as_str())
+                .map_err(|_| serde::de::Error::custom(format!("Invalid header name `{name}`")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::AllowedOrigin;
+
+    fn origin(origin: &str) -> AllowedOrigin {
+        AllowedOrigin::try_from(origin.to_owned()).unwrap()
+    }
+
+    fn matches(allowed: &str, origin: &'static str) -> bool {
+        self::origin(allowed).matches(&HeaderValue::from_static(origin))
+    }
+
+    #[test]
+    fn exact_origins_match_exactly() {
+        assert!(matches("https://app.example", "https://app.example"));
+        assert!(!matches("https://app.example", "http://app.example"));
+        assert!(!matches("https://app.example", "https://app.example:8443"));
+        assert!(!matches("https://app.example", "https://app.example.evil"));
+    }
+
+    #[test]
+    fn wildcards_match_subdomains() {
+        assert!(matches("https://*.example.com", "https://app.example.com"));
+        assert!(matches("https://*.example.com", "https://a.b.example.com"));
+        assert!(matches(
+            "https://*.example.com:8443",
+            "https://app.example.com:8443"
+        ));
+
+        assert!(!matches("https://*.example.com", "https://example.com"));
+        assert!(!matches("https://*.example.com", "http://app.example.com"));
+        assert!(!matches("https://*.example.com", "https://evilexample.com"));
+        assert!(!matches(
+            "https://*.example.com",
+            "https://app.example.com:8443"
+        ));
+        assert!(!matches(
+            "https://*.example.com",
+            "https://evil.com/.example.com"
+        ));
+        assert!(!matches("https://*.example.com", "https://..example.com"));
+    }
+
+    #[test]
+    fn invalid_origins_are_rejected() {
+        for invalid in [
+            "*",
+            "app.example",
+            "https://",
+            "https://app.example/",
+            "https://*example.com",
+            "https://app.*.example.com",
+            "https://*.*.example.com",
+        ] {
+            assert!(
+                AllowedOrigin::try_from(invalid.to_owned()).is_err(),
+                "{invalid}"
+            );
+        }
+
+        assert_eq!(
+            "https://*.example.com",
+            origin("https://*.example.com").to_string()
+        );
+    }
+}
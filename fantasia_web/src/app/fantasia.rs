@@ -1,43 +1,130 @@
-use std::{fmt::Debug, future::Future, io, iter, net::SocketAddr};
+use std::{
+    fmt::{self, Debug},
+    future::Future,
+    io, iter,
+    net::SocketAddr,
+    os::fd::AsFd,
+    sync::Arc,
+    time::Duration,
+};
 
-use axum::{
-    serve::{self},
-    Router,
+use axum::Router;
+use futures::{
+    future::{join_all, JoinAll},
+    FutureExt,
 };
-use futures::future::{join_all, JoinAll};
 use secrecy::ExposeSecret;
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use tokio::net::{self, TcpListener, ToSocketAddrs};
-use tracing::{debug, info, trace};
+use tokio::net::{self, TcpListener, ToSocketAddrs, UnixListener};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, trace, warn};
 
-use crate::{state::State, Serve};
+use super::{
+    activation::{self, InheritedSocket},
+    addr::{ListenAddr, UnixSocket},
+    cors::CorsSettings,
+    http::HttpSettings,
+    limits::ReloadableLimits,
+    listener::{Bind, Listener, ListenerSettings, Protocol, Role},
+    server,
+    shutdown::{self, Shutdown, DEFAULT_GRACE_PERIOD},
+    tls::ReloadableCert,
+    upgrade::ListenerFd,
+};
+use crate::{
+    extract::TrustedProxies,
+    metrics::Metrics,
+    state::{SharedPool, State},
+    Serve,
+};
 
 pub struct FantasiaBuilder {
-    router: Router,
-    sockets: Vec<SocketAddr>,
+    state: State,
+    listeners: Vec<Listener>,
+    certs: Vec<Arc<ReloadableCert>>,
+    pool: SharedPool,
+    admin_routes: Router,
+    http: HttpSettings,
+    limits: Arc<ReloadableLimits>,
+    cors: Option<CorsSettings>,
+    trusted_proxies: TrustedProxies,
+    grace_period: Duration,
 }
 
-#[derive(Debug)]
 pub struct Fantasia {
-    /// Local socket address for this instance.
+    /// Local socket address or Unix socket path for this instance.
     ///
     /// An address that binds to any port, such as `[::]:0`, doesn't reveal its local address until
     /// it is bound.
-    pub sock_addr: SocketAddr,
+    pub sock_addr: ListenAddr,
+    /// Whether this instance serves HTTP or HTTPS.
+    pub protocol: Protocol,
+    /// Role of the listener this instance was bound from.
+    pub role: Role,
+    /// Listening socket for handing over to a new process with [super::upgrade::handoff].
+    pub listener_fd: ListenerFd,
     pub server: Serve,
 }
 
+impl Debug for Fantasia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fantasia")
+            .field("sock_addr", &self.sock_addr)
+            .field("protocol", &self.protocol)
+            .field("role", &self.role)
+            .field("listener_fd", &self.listener_fd)
+            .finish_non_exhaustive()
+    }
+}
+
 impl FantasiaBuilder {
     /// Construct [Fantasia] instances from parsed [SocketAddr]s.
+    ///
+    /// Each address is bound as a public HTTP listener. Use [FantasiaBuilder::listener] to add
+    /// listeners with other settings.
     #[tracing::instrument]
     pub fn new(sockets: &[SocketAddr], pool: PgPool) -> FantasiaBuilder {
-        let sockets = sockets.to_vec();
         debug!("{} socket addresses", sockets.len());
 
-        let state = State { pool };
-        let router = super::router::bind_routes(state);
+        let listeners = sockets
+            .iter()
+            .map(|&addr| Listener {
+                bind: Bind::Tcp(addr),
+                tls: None,
+                role: Role::Public,
+                proxy_protocol: false,
+                name: None,
+            })
+            .collect();
+
+        FantasiaBuilder::with_listeners(listeners, Vec::new(), pool)
+    }
+
+    fn with_listeners(
+        listeners: Vec<Listener>,
+        certs: Vec<Arc<ReloadableCert>>,
+        pool: PgPool,
+    ) -> FantasiaBuilder {
+        let pool = SharedPool::new(pool);
+        let state = State {
+            pool: pool.clone(),
+            metrics: Arc::default(),
+        };
+        let http = HttpSettings::default();
+        let limits = Arc::new(ReloadableLimits::new(&http, state.metrics.clone()));
 
-        FantasiaBuilder { router, sockets }
+        FantasiaBuilder {
+            state,
+            listeners,
+            certs,
+            pool,
+            admin_routes: Router::new(),
+            http,
+            limits,
+            cors: None,
+            trusted_proxies: TrustedProxies::default(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        }
     }
 
     /// Build [Fantasia] instances by resolving network addresses and connecting to Postgres.
@@ -57,42 +144,350 @@ impl FantasiaBuilder {
     where
         S: ExposeSecret<String> + Debug,
     {
-        info!("Retrieving socket addresses");
-
-        // Asynchronously look up provided addresses.
-        // I'm not using the standard library's [std::net::ToSocketAddrs] because that blocks the
-        // executor.
-        let addrs: Vec<_> = net::lookup_host(addrs).await?.collect();
-        if addrs.is_empty() {
-            Err(io::ErrorKind::AddrNotAvailable)?;
+        let addrs = resolve(addrs).await?;
+        let pool = connect(options, url).await?;
+
+        Ok(FantasiaBuilder::new(&addrs, pool))
+    }
+
+    /// Build [Fantasia] instances from listener settings and connect to Postgres.
+    ///
+    /// Every address resolved from an entry is bound with that entry's protocol, TLS settings,
+    /// and role.
+    ///
+    /// # Arguments
+    /// * `settings` - Listeners to bind, such as `[[fantasia.listen]]`
+    /// * `options` - Options for the Postgres [sqlx::PgPool]
+    /// * `url` - Postgres server URL
+    #[tracing::instrument]
+    pub async fn new_from_listeners<S>(
+        settings: &[ListenerSettings],
+        options: PgPoolOptions,
+        url: &S,
+    ) -> io::Result<FantasiaBuilder>
+    where
+        S: ExposeSecret<String> + Debug,
+    {
+        if settings.is_empty() {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "At least one listener is required",
+            ))?;
         }
-        for sockaddr in &addrs {
-            info!("Using address: {sockaddr}");
+
+        let mut listeners = Vec::new();
+        let mut certs = Vec::new();
+        for listener in settings {
+            let tls = listener.acceptor()?.map(|(acceptor, cert)| {
+                certs.push(cert);
+                acceptor
+            });
+
+            if let Some(socket) = listener.unix_socket()? {
+                info!(
+                    "Using {} {} listener: {}",
+                    listener.role, listener.protocol, listener.address
+                );
+                listeners.push(Listener {
+                    bind: Bind::Unix(socket),
+                    tls,
+                    role: listener.role,
+                    proxy_protocol: listener.proxy_protocol,
+                    name: listener.name.clone(),
+                });
+                continue;
+            }
+
+            for addr in resolve(&*listener.address).await? {
+                info!(
+                    "Using {} {} listener: {addr}",
+                    listener.role, listener.protocol
+                );
+                listeners.push(Listener {
+                    bind: Bind::Tcp(addr),
+                    tls: tls.clone(),
+                    role: listener.role,
+                    proxy_protocol: listener.proxy_protocol,
+                    name: listener.name.clone(),
+                });
+            }
         }
 
-        info!("Connecting to Postgres database at `{url:?}`");
-        let pool = options
-            .connect(url.expose_secret())
-            .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        info!("Successfully connected to the Postgres server");
+        let pool = connect(options, url).await?;
+        Ok(FantasiaBuilder::with_listeners(listeners, certs, pool))
+    }
 
-        Ok(FantasiaBuilder::new(&addrs, pool))
+    /// Build [Fantasia] instances from sockets passed by systemd or a previous Fantasia process
+    /// and connect to Postgres.
+    ///
+    /// Falls back to [FantasiaBuilder::new_from_listeners] if no sockets were passed. Passed
+    /// sockets take the settings of the entry whose `name` matches the socket's name or else whose
+    /// `address` matches the socket's local address. Unmatched sockets are served as public HTTP
+    /// listeners.
+    ///
+    /// # Arguments
+    /// * `settings` - Listeners such as `[[fantasia.listen]]`
+    /// * `options` - Options for the Postgres [sqlx::PgPool]
+    /// * `url` - Postgres server URL
+    #[tracing::instrument]
+    pub async fn new_from_activation<S>(
+        settings: &[ListenerSettings],
+        options: PgPoolOptions,
+        url: &S,
+    ) -> io::Result<FantasiaBuilder>
+    where
+        S: ExposeSecret<String> + Debug,
+    {
+        let sockets = activation::listen_fds()?;
+        if sockets.is_empty() {
+            info!("Not socket activated; binding configured addresses");
+            return FantasiaBuilder::new_from_listeners(settings, options, url).await;
+        }
+
+        let local_addrs = sockets
+            .iter()
+            .map(|activated| activated.socket.local_addr())
+            .collect::<io::Result<Vec<_>>>()?;
+        let names: Vec<_> = sockets
+            .iter()
+            .map(|activated| activated.name.as_deref())
+            .collect();
+        let matches = match_settings(settings, &names, &local_addrs).await;
+
+        let mut listeners = Vec::new();
+        let mut certs = Vec::new();
+        for ((activated, local_addr), listener) in sockets.into_iter().zip(local_addrs).zip(matches)
+        {
+            let (tls, role, proxy_protocol) = match listener {
+                Some(listener) => {
+                    let tls = listener.acceptor()?.map(|(acceptor, cert)| {
+                        certs.push(cert);
+                        acceptor
+                    });
+                    (tls, listener.role, listener.proxy_protocol)
+                }
+                None => {
+                    warn!(
+                        "No listener settings for socket {:?} at {local_addr}; serving public HTTP",
+                        activated.name
+                    );
+                    (None, Role::Public, false)
+                }
+            };
+
+            listeners.push(Listener {
+                bind: Bind::Inherited(activated.socket),
+                tls,
+                role,
+                proxy_protocol,
+                name: activated
+                    .name
+                    .or_else(|| listener.and_then(|listener| listener.name.clone())),
+            });
+        }
+
+        let pool = connect(options, url).await?;
+        Ok(FantasiaBuilder::with_listeners(listeners, certs, pool))
+    }
+
+    /// Add a listener on `addr`.
+    ///
+    /// TLS is terminated with `tls` if set.
+    pub fn listener(mut self, addr: SocketAddr, tls: Option<TlsAcceptor>, role: Role) -> Self {
+        self.listeners.push(Listener {
+            bind: Bind::Tcp(addr),
+            tls,
+            role,
+            proxy_protocol: false,
+            name: None,
+        });
+        self
+    }
+
+    /// Add a listener on the Unix domain socket `socket`.
+    ///
+    /// TLS is terminated with `tls` if set.
+    pub fn unix_listener(
+        mut self,
+        socket: UnixSocket,
+        tls: Option<TlsAcceptor>,
+        role: Role,
+    ) -> Self {
+        self.listeners.push(Listener {
+            bind: Bind::Unix(socket),
+            tls,
+            role,
+            proxy_protocol: false,
+            name: None,
+        });
+        self
+    }
+
+    /// Add a listener on a socket that was bound by another process.
+    ///
+    /// TLS is terminated with `tls` if set.
+    pub fn inherited_listener(
+        mut self,
+        socket: InheritedSocket,
+        tls: Option<TlsAcceptor>,
+        role: Role,
+    ) -> Self {
+        self.listeners.push(Listener {
+            bind: Bind::Inherited(socket),
+            tls,
+            role,
+            proxy_protocol: false,
+            name: None,
+        });
+        self
+    }
+
+    /// Time to wait for in-flight requests to finish after a shutdown is requested.
+    ///
+    /// Defaults to [DEFAULT_GRACE_PERIOD].
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Terminate TLS on every listener with `acceptor`.
+    ///
+    /// See [super::tls::TlsSettings::acceptor].
+    pub fn tls(mut self, acceptor: TlsAcceptor) -> Self {
+        for listener in &mut self.listeners {
+            listener.tls = Some(acceptor.clone());
+        }
+        self
+    }
+
+    /// Expect a PROXY protocol header on every listener's connections.
+    ///
+    /// See [super::proxy].
+    pub fn proxy_protocol(mut self, enabled: bool) -> Self {
+        for listener in &mut self.listeners {
+            listener.proxy_protocol = enabled;
+        }
+        self
+    }
+
+    /// Add routes that are only served on [Role::Admin] listeners.
+    ///
+    /// Admin routes are merged with Fantasia's own operational endpoints and never reach public
+    /// listeners. Public routes aren't served on admin listeners either.
+    pub fn admin_routes(mut self, routes: Router) -> Self {
+        self.admin_routes = self.admin_routes.merge(routes);
+        self
+    }
+
+    /// Middleware settings for every route, such as timeouts and compression.
+    pub fn http(mut self, http: HttpSettings) -> Self {
+        self.limits.reload(&http);
+        self.http = http;
+        self
+    }
+
+    /// Allow browsers on other origins to call public routes.
+    ///
+    /// Cross-origin requests aren't allowed by default.
+    pub fn cors(mut self, cors: CorsSettings) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+
+    /// Reverse proxies whose forwarding headers are trusted when resolving [ClientIp].
+    ///
+    /// No proxies are trusted by default, so the client is always the connection's peer.
+    ///
+    /// [ClientIp]: crate::extract::ClientIp
+    pub fn trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+
+    /// TLS certificates loaded from listener settings.
+    ///
+    /// Reload them to rotate certificates without restarting. Acceptors added with
+    /// [FantasiaBuilder::tls] or the listener methods aren't included.
+    pub fn certificates(&self) -> &[Arc<ReloadableCert>] {
+        &self.certs
+    }
+
+    /// Request timeout and rate limits shared by every [Fantasia] instance.
+    ///
+    /// Reload them to apply new limits without restarting. The other [HttpSettings] are fixed once
+    /// the servers are built.
+    pub fn limits(&self) -> &Arc<ReloadableLimits> {
+        &self.limits
+    }
+
+    /// Metrics shared by every [Fantasia] instance.
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.state.metrics
+    }
+
+    /// Postgres pool shared by every [Fantasia] instance.
+    ///
+    /// The pool isn't closed when the servers stop so that the caller may close it after every
+    /// instance has drained. Replacing the pool applies to requests that start afterwards.
+    pub fn pool(&self) -> &SharedPool {
+        &self.pool
     }
 
     /// Build a running server from a [Fantasia] instance.
+    ///
+    /// Each server stops accepting connections once `shutdown` is triggered and then waits up to
+    /// the builder's grace period for in-flight requests.
     #[tracing::instrument(skip(self))]
-    pub fn into_server(self) -> JoinAll<impl Future<Output = io::Result<Fantasia>>> {
+    pub fn into_server(
+        self,
+        shutdown: &Shutdown,
+    ) -> JoinAll<impl Future<Output = io::Result<Fantasia>>> {
         trace!("Binding to sockets");
 
-        let Self { sockets, router } = self;
+        let Self {
+            state,
+            listeners,
+            admin_routes,
+            http,
+            limits,
+            cors,
+            trusted_proxies,
+            grace_period,
+            ..
+        } = self;
+        // Metrics are private to admin listeners if there are any
+        let public_metrics = !listeners
+            .iter()
+            .any(|listener| listener.role == Role::Admin);
+        let routers = Routers {
+            public: super::router::bind_routes(
+                state.clone(),
+                &http,
+                limits.clone(),
+                trusted_proxies.clone(),
+                cors.as_ref(),
+                public_metrics,
+            ),
+            admin: super::router::bind_admin_routes(
+                state,
+                admin_routes,
+                &http,
+                limits,
+                trusted_proxies,
+            ),
+        };
 
         join_all(
-            sockets
+            listeners
                 .into_iter()
-                // `router` needs to be cloned and moved into the async closure
-                .zip(iter::repeat(router))
-                .inspect(|(addr, _)| info!("Asynchronously binding to socket address: {addr}"))
+                // `routers` needs to be cloned and moved into the async closure
+                .zip(iter::repeat((routers, shutdown.clone())))
+                .inspect(|(listener, _)| {
+                    info!(
+                        "Asynchronously binding to socket address: {:?}",
+                        listener.bind
+                    )
+                })
                 // I'm not sure how to return a Result<JoinAll<_>, _> that simply evaluates to a
                 // future that yields `Serve`. This returns
                 // `JoinAll<impl Future<Output = io::Result<Fantasia>>>`
@@ -100,21 +495,212 @@ impl FantasiaBuilder {
                 // followed by handling any errors followed by awaiting the actual servers
                 // (Actually, this may be a good thing for maximum flexibility but it seems kind of
                 // ugly to me...but what do I know?)
-                .map(|(addr, router)| async move {
-                    TcpListener::bind(addr).await.and_then(|listener| {
-                        Ok(Fantasia {
-                            sock_addr: listener.local_addr()?,
-                            server: serve::serve(
-                                listener,
-                                router.into_make_service_with_connect_info::<SocketAddr>(),
-                            ),
-                        })
-                    })
+                .map(move |(listener, (routers, shutdown))| {
+                    let router = match listener.role {
+                        Role::Public => routers.public,
+                        Role::Admin => routers.admin,
+                    };
+                    bind(listener, router, shutdown, grace_period)
                 }),
         )
     }
 }
 
+/// Router for each [Role].
+#[derive(Clone)]
+struct Routers {
+    public: Router,
+    admin: Router,
+}
+
+/// Bind `listener` and wrap its accept loop in a [Fantasia] instance.
+async fn bind(
+    listener: Listener,
+    router: Router,
+    shutdown: Shutdown,
+    grace_period: Duration,
+) -> io::Result<Fantasia> {
+    let Listener {
+        bind,
+        tls,
+        role,
+        proxy_protocol,
+        name,
+    } = listener;
+    let protocol = if tls.is_some() {
+        Protocol::Https
+    } else {
+        Protocol::Http
+    };
+
+    // Keep a duplicate of the socket around in case it's handed over to a new process
+    let tcp = |listener: TcpListener| -> io::Result<(ListenAddr, ListenerFd, Serve)> {
+        let sock_addr = ListenAddr::Tcp(listener.local_addr()?);
+        let listener_fd = ListenerFd::new(
+            listener.as_fd().try_clone_to_owned()?,
+            name.clone(),
+            Arc::default(),
+        );
+        let server = server::serve(
+            listener,
+            router.clone(),
+            tls.clone(),
+            proxy_protocol,
+            shutdown.clone(),
+        );
+
+        Ok((sock_addr, listener_fd, server.boxed()))
+    };
+
+    let (sock_addr, listener_fd, server) = match bind {
+        Bind::Tcp(addr) => tcp(TcpListener::bind(addr).await?)?,
+        Bind::Inherited(InheritedSocket::Tcp(listener)) => {
+            listener.set_nonblocking(true)?;
+            tcp(TcpListener::from_std(listener)?)?
+        }
+        Bind::Inherited(InheritedSocket::Unix(listener)) => {
+            listener.set_nonblocking(true)?;
+            let listener = UnixListener::from_std(listener)?;
+            // Abstract and unnamed sockets don't have a path
+            let path = listener
+                .local_addr()?
+                .as_pathname()
+                .map(ToOwned::to_owned)
+                .unwrap_or_default();
+            let listener_fd =
+                ListenerFd::new(listener.as_fd().try_clone_to_owned()?, name, Arc::default());
+            let server = server::serve(listener, router, tls, proxy_protocol, shutdown.clone());
+
+            // The socket file belongs to whoever bound it so it isn't removed on shutdown
+            (ListenAddr::Unix(path), listener_fd, server.boxed())
+        }
+        Bind::Unix(socket) => {
+            let (listener, guard) = socket.bind()?;
+            let listener_fd = ListenerFd::new(
+                listener.as_fd().try_clone_to_owned()?,
+                name,
+                guard.handed_over(),
+            );
+            let server = server::serve(listener, router, tls, proxy_protocol, shutdown.clone());
+
+            // The socket file is removed once the server stops or is dropped
+            let server = async move {
+                let _guard = guard;
+                server.await
+            };
+            (ListenAddr::Unix(socket.path), listener_fd, server.boxed())
+        }
+    };
+
+    Ok(Fantasia {
+        sock_addr,
+        protocol,
+        role,
+        listener_fd,
+        server: shutdown::drain(server, shutdown, grace_period).boxed(),
+    })
+}
+
+/// Asynchronously look up provided addresses.
+///
+/// I'm not using the standard library's [std::net::ToSocketAddrs] because that blocks the
+/// executor.
+async fn resolve(addrs: impl ToSocketAddrs) -> io::Result<Vec<SocketAddr>> {
+    info!("Retrieving socket addresses");
+
+    let addrs: Vec<_> = net::lookup_host(addrs).await?.collect();
+    if addrs.is_empty() {
+        Err(io::ErrorKind::AddrNotAvailable)?;
+    }
+    for sockaddr in &addrs {
+        info!("Using address: {sockaddr}");
+    }
+
+    Ok(addrs)
+}
+
+/// Settings for each inherited socket, matched by name or else by local address.
+///
+/// Names, socket paths, and literal addresses are matched first. Only entries that are still
+/// unmatched are resolved, and entries that fail to resolve are skipped so that one bad host
+/// doesn't prevent the other sockets from being served.
+async fn match_settings<'s>(
+    settings: &'s [ListenerSettings],
+    names: &[Option<&str>],
+    local_addrs: &[ListenAddr],
+) -> Vec<Option<&'s ListenerSettings>> {
+    let mut matches: Vec<_> = names
+        .iter()
+        .zip(local_addrs)
+        .map(|(name, local_addr)| {
+            let by_name = name.and_then(|name| {
+                settings
+                    .iter()
+                    .find(|listener| listener.name.as_deref() == Some(name))
+            });
+            by_name.or_else(|| {
+                settings.iter().find(|listener| match local_addr {
+                    ListenAddr::Tcp(addr) => listener.address.parse() == Ok(*addr),
+                    ListenAddr::Unix(path) => listener.unix_path() == Some(path.as_path()),
+                })
+            })
+        })
+        .collect();
+
+    let unmatched_tcp = matches
+        .iter()
+        .zip(local_addrs)
+        .any(|(listener, local_addr)| {
+            listener.is_none() && matches!(local_addr, ListenAddr::Tcp(_))
+        });
+    if !unmatched_tcp {
+        return matches;
+    }
+
+    let mut resolved = Vec::new();
+    for candidate in settings {
+        let matched = matches
+            .iter()
+            .any(|listener| listener.is_some_and(|listener| std::ptr::eq(listener, candidate)));
+        if matched
+            || candidate.unix_path().is_some()
+            || candidate.address.parse::<SocketAddr>().is_ok()
+        {
+            continue;
+        }
+
+        match resolve(&*candidate.address).await {
+            Ok(addrs) => resolved.push((candidate, addrs)),
+            Err(e) => warn!("Skipping listener `{}`: {e}", candidate.address),
+        }
+    }
+
+    for (listener, local_addr) in matches.iter_mut().zip(local_addrs) {
+        if let (None, ListenAddr::Tcp(addr)) = (&listener, local_addr) {
+            *listener = resolved
+                .iter()
+                .find(|(_, addrs)| addrs.contains(addr))
+                .map(|(candidate, _)| *candidate);
+        }
+    }
+
+    matches
+}
+
+async fn connect<S>(options: PgPoolOptions, url: &S) -> io::Result<PgPool>
+where
+    S: ExposeSecret<String> + Debug,
+{
+    info!("Connecting to Postgres database at `{url:?}`");
+    let pool = options
+        .connect(url.expose_secret())
+        .await
+        .map_err(io::Error::other)?;
+    info!("Successfully connected to the Postgres server");
+
+    Ok(pool)
+}
+
 // impl TryInto<Server<AddrIncoming, IntoMakeService<Router>>> for Fantasia {
 //     type Error = hyper::Error;
 //
@@ -125,5 +711,51 @@ impl FantasiaBuilder {
 
 #[cfg(test)]
 mod tests {
-    use super::FantasiaBuilder;
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use super::{match_settings, ListenAddr, ListenerSettings};
+
+    fn settings() -> Vec<ListenerSettings> {
+        serde_json::from_value(json!([
+            { "address": "unresolvable.invalid:8000" },
+            { "address": "127.0.0.1:8000" },
+            { "address": "unix:/run/fantasia.sock" },
+            { "address": "unresolvable.invalid:8443", "name": "https" },
+            { "address": "localhost:8001" },
+        ]))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sockets_match_without_resolving() {
+        let settings = settings();
+        let local_addrs = [
+            ListenAddr::Tcp("127.0.0.1:8000".parse().unwrap()),
+            ListenAddr::Unix(PathBuf::from("/run/fantasia.sock")),
+            ListenAddr::Tcp("127.0.0.1:9000".parse().unwrap()),
+        ];
+
+        let matches = match_settings(&settings, &[None, None, Some("https")], &local_addrs).await;
+
+        assert_eq!(
+            vec![Some(&settings[1]), Some(&settings[2]), Some(&settings[3])],
+            matches
+        );
+    }
+
+    #[tokio::test]
+    async fn unmatched_sockets_resolve_remaining_hosts() {
+        let settings = settings();
+        let local_addrs = [
+            ListenAddr::Tcp("127.0.0.1:8001".parse().unwrap()),
+            ListenAddr::Tcp("127.0.0.1:9000".parse().unwrap()),
+        ];
+
+        // The unresolvable entries are skipped rather than failing every socket
+        let matches = match_settings(&settings, &[None, None], &local_addrs).await;
+
+        assert_eq!(vec![Some(&settings[4]), None], matches);
+    }
 }
@@ -0,0 +1,127 @@
+//! HTTP middleware settings, e.g. `[fantasia.http]`.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
+use tower_http::compression;
+
+use super::{
+    rate_limit::RateLimitSettings, security_headers::SecurityHeaders, trace::RedactedHeaders,
+};
+
+/// Default request time limit.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default request body limit, which is the same as [axum]'s.
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Middleware applied to every route.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct HttpSettings {
+    /// Time to handle a request before responding with `408 Request Timeout`. Zero disables the
+    /// limit.
+    #[serde(
+        deserialize_with = "deserialize_seconds",
+        alias = "request_timeout_seconds"
+    )]
+    pub request_timeout: Duration,
+    /// Largest request body in bytes that extractors accept.
+    pub body_limit: usize,
+    /// How request paths are rewritten before routing.
+    pub normalize_path: NormalizePath,
+    /// Response compression and request decompression.
+    pub compression: CompressionSettings,
+    /// Headers whose values are replaced with `[redacted]` in request spans and response events.
+    pub redact_headers: RedactedHeaders,
+    /// Per-client rate limits for public routes. Disabled if unset.
+    pub rate_limit: Option<RateLimitSettings>,
+    /// Security headers added to responses that don't set them.
+    pub security_headers: SecurityHeaders,
+}
+
+/// Request path normalization.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizePath {
+    /// Route paths as they were sent.
+    None,
+    /// Remove trailing slashes so that `/health_check/` is routed to `/health_check`.
+    #[default]
+    TrimTrailingSlash,
+}
+
+/// Compression algorithms and how hard to compress.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct CompressionSettings {
+    pub gzip: bool,
+    pub deflate: bool,
+    pub br: bool,
+    /// Level of each algorithm, since their numeric levels have different ranges.
+    pub gzip_level: CompressionLevel,
+    pub deflate_level: CompressionLevel,
+    pub br_level: CompressionLevel,
+    /// Smallest response body in bytes that's compressed.
+    pub min_size: u16,
+}
+
+/// Compression level, either named or the algorithm's own numeric level (e.g. `9` for gzip).
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionLevel {
+    Fastest,
+    Best,
+    #[default]
+    Default,
+    /// Clamped to the algorithm's maximum.
+    #[serde(untagged)]
+    Precise(i32),
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        HttpSettings {
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            body_limit: DEFAULT_BODY_LIMIT,
+            normalize_path: NormalizePath::default(),
+            compression: CompressionSettings::default(),
+            redact_headers: RedactedHeaders::default(),
+            rate_limit: None,
+            security_headers: SecurityHeaders::default(),
+        }
+    }
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        CompressionSettings {
+            gzip: true,
+            deflate: true,
+            br: true,
+            gzip_level: CompressionLevel::default(),
+            deflate_level: CompressionLevel::default(),
+            br_level: CompressionLevel::default(),
+            // Same as tower-http's default predicate
+            min_size: 32,
+        }
+    }
+}
+
+impl From<CompressionLevel> for compression::CompressionLevel {
+    fn from(level: CompressionLevel) -> Self {
+        match level {
+            CompressionLevel::Fastest => compression::CompressionLevel::Fastest,
+            CompressionLevel::Best => compression::CompressionLevel::Best,
+            CompressionLevel::Default => compression::CompressionLevel::Default,
+            CompressionLevel::Precise(level) => compression::CompressionLevel::Precise(level),
+        }
+    }
+}
+
+pub(super) fn deserialize_seconds<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    u64::deserialize(deserializer).map(Duration::from_secs)
+}
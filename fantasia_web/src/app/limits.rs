@@ -0,0 +1,84 @@
+//! Request limits that may change while Fantasia is running.
+
+use std::{
+    fmt::{self, Debug},
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::time;
+use tracing::debug;
+
+use super::{http::HttpSettings, rate_limit::RateLimiter};
+use crate::metrics::Metrics;
+
+/// Request timeout and rate limits from [HttpSettings].
+///
+/// Every router built by the same [super::FantasiaBuilder] shares these limits and reads them for
+/// each request, so reloading them applies to requests that start afterwards.
+pub struct ReloadableLimits {
+    request_timeout: RwLock<Duration>,
+    pub(crate) rate_limiter: Arc<RateLimiter>,
+}
+
+impl ReloadableLimits {
+    pub(crate) fn new(http: &HttpSettings, metrics: Arc<Metrics>) -> Self {
+        ReloadableLimits {
+            request_timeout: RwLock::new(http.request_timeout),
+            rate_limiter: Arc::new(RateLimiter::new(http.rate_limit.as_ref(), metrics)),
+        }
+    }
+
+    /// Apply the request timeout and rate limits from `http`.
+    ///
+    /// Clients start with full buckets if the rate limits changed.
+    #[tracing::instrument(skip_all)]
+    pub fn reload(&self, http: &HttpSettings) {
+        *self
+            .request_timeout
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = http.request_timeout;
+        self.rate_limiter.reload(http.rate_limit.as_ref());
+
+        debug!("Applied request limits");
+    }
+
+    fn request_timeout(&self) -> Duration {
+        *self
+            .request_timeout
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Debug for ReloadableLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadableLimits")
+            .field("request_timeout", &self.request_timeout())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Respond with `408 Request Timeout` to requests that take longer than the current timeout.
+///
+/// A zero timeout disables the limit.
+pub(crate) async fn timeout(
+    State(limits): State<Arc<ReloadableLimits>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let timeout = limits.request_timeout();
+    if timeout.is_zero() {
+        return next.run(request).await;
+    }
+
+    time::timeout(timeout, next.run(request))
+        .await
+        .unwrap_or_else(|_| StatusCode::REQUEST_TIMEOUT.into_response())
+}
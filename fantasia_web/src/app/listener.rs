@@ -0,0 +1,197 @@
+//! Per-listener settings.
+
+use std::{
+    fmt::{self, Display},
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use nix::unistd::{Group, User};
+use serde::Deserialize;
+use tokio_rustls::TlsAcceptor;
+
+use super::{
+    activation::InheritedSocket,
+    addr::UnixSocket,
+    tls::{ReloadableCert, TlsSettings},
+};
+
+/// Prefix for Unix domain socket addresses in [ListenerSettings::address].
+const UNIX_PREFIX: &str = "unix:";
+
+/// Settings for a single listener, e.g. an entry in `[[fantasia.listen]]`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerSettings {
+    /// Host and port to bind (e.g. `localhost:8000` or `[::1]:8443`) or a Unix domain socket
+    /// path prefixed with `unix:` (e.g. `unix:/run/fantasia/fantasia.sock`).
+    ///
+    /// Every address the host resolves to is bound with the same settings.
+    pub address: String,
+    /// Serve plain HTTP or HTTPS.
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// TLS settings for HTTPS listeners.
+    pub tls: Option<TlsSettings>,
+    /// Which routes this listener serves.
+    #[serde(default)]
+    pub role: Role,
+    /// Expect a PROXY protocol v1 or v2 header on every connection (e.g. behind HAProxy or a TCP
+    /// load balancer).
+    ///
+    /// Connections without a valid header are rejected.
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    /// File mode of a Unix domain socket (e.g. `0o660`).
+    pub mode: Option<u32>,
+    /// User name or ID that owns a Unix domain socket.
+    pub owner: Option<String>,
+    /// Group name or ID that owns a Unix domain socket.
+    pub group: Option<String>,
+    /// Name of a socket-activated file descriptor (systemd's `FileDescriptorName=`).
+    ///
+    /// If Fantasia is socket activated, this listener's settings apply to the passed socket with
+    /// this name instead of binding `address`. Unnamed passed sockets are matched by `address`.
+    pub name: Option<String>,
+}
+
+/// Application protocol served by a listener.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Http,
+    Https,
+}
+
+/// Audience of a listener.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Serves the public API.
+    #[default]
+    Public,
+    /// Serves operational endpoints that must not be exposed to the internet.
+    Admin,
+}
+
+/// Resolved listener ready to be bound by [super::FantasiaBuilder].
+pub(crate) struct Listener {
+    pub bind: Bind,
+    pub tls: Option<TlsAcceptor>,
+    pub role: Role,
+    /// Read the client's address from a PROXY protocol header.
+    pub proxy_protocol: bool,
+    /// Name of the socket when it's handed over to another process.
+    pub name: Option<String>,
+}
+
+/// Socket to bind for a [Listener].
+#[derive(Debug)]
+pub(crate) enum Bind {
+    Tcp(SocketAddr),
+    Unix(UnixSocket),
+    /// Already bound by another process.
+    Inherited(InheritedSocket),
+}
+
+impl ListenerSettings {
+    /// Load the TLS acceptor for this listener, if any, along with its certificate.
+    ///
+    /// HTTPS listeners must have TLS settings, and HTTP listeners must not.
+    pub fn acceptor(&self) -> io::Result<Option<(TlsAcceptor, Arc<ReloadableCert>)>> {
+        match (self.protocol, &self.tls) {
+            (Protocol::Https, Some(tls)) => tls.reloadable_acceptor().map(Some),
+            (Protocol::Http, None) => Ok(None),
+            (Protocol::Https, None) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("HTTPS listener `{}` has no TLS settings", self.address),
+            )),
+            (Protocol::Http, Some(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("HTTP listener `{}` has TLS settings", self.address),
+            )),
+        }
+    }
+
+    /// Path of a `unix:` address.
+    pub fn unix_path(&self) -> Option<&Path> {
+        self.address.strip_prefix(UNIX_PREFIX).map(Path::new)
+    }
+
+    /// Unix domain socket for `unix:` addresses.
+    ///
+    /// Returns `None` for TCP addresses. Socket permissions are only valid for Unix sockets.
+    pub fn unix_socket(&self) -> io::Result<Option<UnixSocket>> {
+        match self.address.strip_prefix(UNIX_PREFIX) {
+            Some(path) => Ok(Some(UnixSocket {
+                path: PathBuf::from(path),
+                mode: self.mode,
+                uid: self.owner.as_deref().map(uid).transpose()?,
+                gid: self.group.as_deref().map(gid).transpose()?,
+            })),
+            None if self.mode.is_some() || self.owner.is_some() || self.group.is_some() => {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "`mode`, `owner`, and `group` require a Unix socket (`{}`)",
+                        self.address
+                    ),
+                ))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// Look up a user by name unless `owner` is already a numeric ID
+fn uid(owner: &str) -> io::Result<u32> {
+    if let Ok(uid) = owner.parse() {
+        return Ok(uid);
+    }
+
+    User::from_name(owner)?
+        .map(|user| user.uid.as_raw())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("User `{owner}` does not exist"),
+            )
+        })
+}
+
+// Look up a group by name unless `group` is already a numeric ID
+fn gid(group: &str) -> io::Result<u32> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+
+    Group::from_name(group)?
+        .map(|group| group.gid.as_raw())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Group `{group}` does not exist"),
+            )
+        })
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::Http => write!(f, "http"),
+            Protocol::Https => write!(f, "https"),
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Public => write!(f, "public"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
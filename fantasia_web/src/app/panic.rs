@@ -0,0 +1,84 @@
+//! Responses for handlers that panic.
+//!
+//! Without [tower_http::catch_panic], a panicking handler drops the connection without a response.
+//! Instead, the panic is logged within the request's span and the client gets a
+//! `500 Internal Server Error` problem.
+//!
+//! The payload that reaches the layer doesn't carry a backtrace. Install [install_hook] from `main`
+//! to record the backtrace of the panicking thread first, or only the payload is logged.
+
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    cell::RefCell,
+    panic,
+    sync::{Arc, Once},
+};
+
+use axum::{body::Body, http::StatusCode, response::IntoResponse};
+use tower_http::catch_panic::{CatchPanicLayer, ResponseForPanic};
+use tracing::error;
+
+use crate::{error::Problem, metrics::Metrics};
+
+thread_local! {
+    /// Backtrace of the last panic on this thread.
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+static HOOK: Once = Once::new();
+
+/// Logs panics and responds with a [Problem].
+#[derive(Debug, Clone)]
+pub(crate) struct PanicResponse {
+    metrics: Arc<Metrics>,
+}
+
+/// Record backtraces of panics for the responses of panicking handlers.
+///
+/// Backtraces are only captured if `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` enable them. The
+/// previous hook still runs afterwards, and installing the hook again does nothing.
+pub fn install_hook() {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let captured = Backtrace::capture();
+            if captured.status() == BacktraceStatus::Captured {
+                BACKTRACE.with(|backtrace| *backtrace.borrow_mut() = Some(captured));
+            }
+            previous(info);
+        }));
+    });
+}
+
+impl PanicResponse {
+    pub(crate) fn layer(metrics: Arc<Metrics>) -> CatchPanicLayer<Self> {
+        CatchPanicLayer::custom(PanicResponse { metrics })
+    }
+}
+
+impl ResponseForPanic for PanicResponse {
+    type ResponseBody = Body;
+
+    fn response_for_panic(
+        &mut self,
+        payload: Box<dyn Any + Send + 'static>,
+    ) -> axum::http::Response<Body> {
+        // Payloads are almost always `panic!`'s formatted message or a string literal
+        let message = payload
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| payload.downcast_ref::<&str>().copied())
+            .unwrap_or("Box<dyn Any>");
+        // The layer catches the panic on the thread that panicked
+        let backtrace = BACKTRACE.with(|backtrace| backtrace.borrow_mut().take());
+
+        self.metrics.panics.inc();
+        match backtrace {
+            Some(backtrace) => error!(%backtrace, "Handler panicked: {message}"),
+            None => error!("Handler panicked: {message}"),
+        }
+
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR).into_response()
+    }
+}
@@ -0,0 +1,278 @@
+//! HAProxy [PROXY protocol](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt) v1 and v2.
+//!
+//! Load balancers that forward TCP connections send a PROXY header before any other data so that
+//! the server learns the client's address instead of the balancer's. Listeners that enable the
+//! protocol reject connections whose header is missing or malformed.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Signature that starts every v2 header.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// A v1 header including the trailing CRLF is at most 107 bytes.
+const V1_MAX_LEN: usize = 107;
+
+/// Read a PROXY header from the start of `stream`.
+///
+/// Returns the client's address or `None` if the header doesn't carry one, such as v1's `UNKNOWN`
+/// or v2's `LOCAL` health checks, in which case the connection's own peer address applies. Only
+/// the header is consumed so the rest of the stream can be served as usual.
+///
+/// The caller limits how long the client may take to send it.
+pub(crate) async fn read_header<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    // Both versions are at least as long as the v2 signature
+    let mut prefix = [0; V2_SIGNATURE.len()];
+    stream.read_exact(&mut prefix).await?;
+
+    if prefix == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if prefix.starts_with(b"PROXY ") {
+        read_v1(stream, prefix).await
+    } else {
+        Err(invalid("Missing PROXY header"))
+    }
+}
+
+/// Text header, e.g. `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n`.
+async fn read_v1<S>(
+    stream: &mut S,
+    prefix: [u8; V2_SIGNATURE.len()],
+) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut line = prefix.to_vec();
+
+    // The header has no length field, so read one byte at a time to avoid consuming the request
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("PROXY v1 header is too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+    parse_v1(line)
+}
+
+fn parse_v1(line: &str) -> io::Result<Option<SocketAddr>> {
+    let mut fields = line.split(' ');
+    if fields.next() != Some("PROXY") {
+        return Err(invalid("Missing PROXY header"));
+    }
+
+    let family = fields.next();
+    if family == Some("UNKNOWN") {
+        // Anything after `UNKNOWN` is ignored
+        return Ok(None);
+    }
+
+    let (Some(source), Some(_destination), Some(port), Some(_destination_port), None) = (
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+    ) else {
+        return Err(invalid("PROXY v1 header has the wrong number of fields"));
+    };
+
+    let ip = match family {
+        Some("TCP4") => source.parse::<Ipv4Addr>().map(IpAddr::V4),
+        Some("TCP6") => source.parse::<Ipv6Addr>().map(IpAddr::V6),
+        _ => return Err(invalid("Unsupported PROXY v1 protocol")),
+    }
+    .map_err(|_| invalid("Invalid PROXY v1 source address"))?;
+    let port = port
+        .parse()
+        .map_err(|_| invalid("Invalid PROXY v1 source port"))?;
+
+    Ok(Some(SocketAddr::new(ip, port)))
+}
+
+/// Binary header following [V2_SIGNATURE].
+async fn read_v2<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).await?;
+    let [version_command, family, len @ ..] = header;
+
+    let mut payload = vec![0; u16::from_be_bytes(len).into()];
+    stream.read_exact(&mut payload).await?;
+
+    parse_v2(version_command, family, &payload)
+}
+
+fn parse_v2(version_command: u8, family: u8, payload: &[u8]) -> io::Result<Option<SocketAddr>> {
+    if version_command >> 4 != 2 {
+        return Err(invalid("Unsupported PROXY protocol version"));
+    }
+
+    match version_command & 0x0f {
+        // LOCAL: connections made by the proxy itself, such as health checks
+        0x0 => return Ok(None),
+        // PROXY
+        0x1 => {}
+        _ => return Err(invalid("Unsupported PROXY v2 command")),
+    }
+
+    // The low nibble is the transport, which doesn't matter for the client's address
+    match family >> 4 {
+        // AF_INET: source address, destination address, source port, destination port
+        0x1 => {
+            let addrs: &[u8; 12] = payload
+                .get(..12)
+                .and_then(|addrs| addrs.try_into().ok())
+                .ok_or_else(|| invalid("PROXY v2 IPv4 addresses are truncated"))?;
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&addrs[..4]).unwrap());
+            let port = u16::from_be_bytes([addrs[8], addrs[9]]);
+
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_INET6
+        0x2 => {
+            let addrs: &[u8; 36] = payload
+                .get(..36)
+                .and_then(|addrs| addrs.try_into().ok())
+                .ok_or_else(|| invalid("PROXY v2 IPv6 addresses are truncated"))?;
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&addrs[..16]).unwrap());
+            let port = u16::from_be_bytes([addrs[32], addrs[33]]);
+
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_UNSPEC and AF_UNIX don't carry an IP address
+        0x0 | 0x3 => Ok(None),
+        _ => Err(invalid("Unsupported PROXY v2 address family")),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::{read_header, V2_SIGNATURE};
+
+    async fn read(mut input: &[u8]) -> std::io::Result<(Option<SocketAddr>, Vec<u8>)> {
+        let addr = read_header(&mut input).await?;
+        let mut rest = Vec::new();
+        input.read_to_end(&mut rest).await?;
+
+        Ok((addr, rest))
+    }
+
+    fn v2(command: u8, family: u8, payload: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x20 | command, family]);
+        header.extend(u16::try_from(payload.len()).unwrap().to_be_bytes());
+        header.extend(payload);
+        header.extend(b"GET / HTTP/1.1\r\n");
+        header
+    }
+
+    #[tokio::test]
+    async fn v1_tcp4() {
+        let (addr, rest) = read(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET /")
+            .await
+            .unwrap();
+
+        assert_eq!(Some("192.0.2.1:56324".parse().unwrap()), addr);
+        assert_eq!(b"GET /", &*rest);
+    }
+
+    #[tokio::test]
+    async fn v1_tcp6() {
+        let (addr, _) = read(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n")
+            .await
+            .unwrap();
+
+        assert_eq!(Some("[2001:db8::1]:56324".parse().unwrap()), addr);
+    }
+
+    #[tokio::test]
+    async fn v1_unknown() {
+        let (addr, rest) = read(b"PROXY UNKNOWN\r\nGET /").await.unwrap();
+
+        assert_eq!(None, addr);
+        assert_eq!(b"GET /", &*rest);
+    }
+
+    #[tokio::test]
+    async fn v1_rejects_malformed_headers() {
+        for input in [
+            &b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"[..],
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 56324\r\n",
+            b"PROXY TCP4 2001:db8::1 198.51.100.1 56324 443\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 65536 443\r\n",
+            b"PROXY UDP4 192.0.2.1 198.51.100.1 56324 443\r\n",
+            &[b"PROXY TCP4 ".as_slice(), &[b'1'; 120]].concat(),
+        ] {
+            assert!(read(input).await.is_err(), "{}", input.escape_ascii());
+        }
+    }
+
+    #[tokio::test]
+    async fn v2_inet() {
+        let payload = [192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb];
+        let (addr, rest) = read(&v2(0x1, 0x11, &payload)).await.unwrap();
+
+        assert_eq!(Some("192.0.2.1:56324".parse().unwrap()), addr);
+        assert_eq!(b"GET / HTTP/1.1\r\n", &*rest);
+    }
+
+    #[tokio::test]
+    async fn v2_inet6_with_tlvs() {
+        let source: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let destination: std::net::Ipv6Addr = "2001:db8::2".parse().unwrap();
+        let mut payload = Vec::new();
+        payload.extend(source.octets());
+        payload.extend(destination.octets());
+        payload.extend([0xdc, 0x04, 0x01, 0xbb]);
+        // PP2_TYPE_AUTHORITY TLV
+        payload.extend([0x02, 0x00, 0x09]);
+        payload.extend(b"localhost");
+
+        let (addr, rest) = read(&v2(0x1, 0x21, &payload)).await.unwrap();
+
+        assert_eq!(Some("[2001:db8::1]:56324".parse().unwrap()), addr);
+        assert_eq!(b"GET / HTTP/1.1\r\n", &*rest);
+    }
+
+    #[tokio::test]
+    async fn v2_local() {
+        let (addr, rest) = read(&v2(0x0, 0x00, &[])).await.unwrap();
+
+        assert_eq!(None, addr);
+        assert_eq!(b"GET / HTTP/1.1\r\n", &*rest);
+    }
+
+    #[tokio::test]
+    async fn v2_rejects_malformed_headers() {
+        // Truncated addresses
+        assert!(read(&v2(0x1, 0x11, &[192, 0, 2, 1])).await.is_err());
+        // Unknown command
+        assert!(read(&v2(0x2, 0x11, &[0; 12])).await.is_err());
+        // Unknown family
+        assert!(read(&v2(0x1, 0x41, &[0; 12])).await.is_err());
+
+        let mut wrong_version = v2(0x1, 0x11, &[0; 12]);
+        wrong_version[12] = 0x11;
+        assert!(read(&wrong_version).await.is_err());
+    }
+}
@@ -0,0 +1,597 @@
+//! Per-client rate limiting with token buckets.
+//!
+//! Every client gets a bucket of `burst` tokens per route group which refills at
+//! `requests_per_minute`. Each request takes a token, and requests that find the bucket empty are
+//! rejected with `429 Too Many Requests`. Buckets live in memory, so limits apply per instance.
+//!
+//! IPv6 clients are limited per `/64` network, since a single host is usually free to pick any
+//! address in its `/64`. At most [MAX_BUCKETS] buckets are kept, and the ones that were used least
+//! recently are dropped once there are too many.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    net::{IpAddr, Ipv6Addr},
+    num::NonZeroU32,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::{error::Problem, extract::ClientIp, metrics::Metrics};
+
+/// Label of the limit for routes that don't belong to a group.
+pub const DEFAULT_GROUP: &str = "default";
+
+/// Buckets that haven't been used for this long are dropped once they're full again.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Most buckets kept for every client and route group together.
+pub const MAX_BUCKETS: usize = 100_000;
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Rate limits for public routes, e.g. `[fantasia.http.rate_limit]`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitSettings {
+    /// Limit for routes that don't belong to a group in `routes`.
+    #[serde(flatten)]
+    pub limit: Limit,
+    /// Route groups with their own limits and buckets.
+    #[serde(default)]
+    pub routes: Vec<RouteLimit>,
+}
+
+/// Size and refill rate of a token bucket.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    /// Requests a client may send at once.
+    pub burst: NonZeroU32,
+    /// Rate at which a client's allowance refills.
+    pub requests_per_minute: NonZeroU32,
+}
+
+/// Limit for the routes under a path prefix.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RouteLimit {
+    /// Path prefix of the group (e.g. `/auth` covers `/auth` and `/auth/login`).
+    ///
+    /// Requests belong to the group with the longest matching prefix.
+    pub prefix: String,
+    #[serde(flatten)]
+    pub limit: Limit,
+}
+
+/// Identity that requests are rate limited by.
+///
+/// Requests are limited by [ClientIp] unless authentication middleware inserts a key as a request
+/// extension. Local clients on Unix sockets aren't limited without a key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// Client address. Use [RateLimitKey::ip] to limit IPv6 clients by their network.
+    Ip(IpAddr),
+    /// Authenticated user or API token.
+    Identity(String),
+}
+
+/// Token buckets for every client and route group.
+pub(crate) struct RateLimiter {
+    buckets: Mutex<Buckets>,
+    metrics: Arc<Metrics>,
+}
+
+// Limits live with the buckets so that reloading can't pair a bucket with another group's limit
+struct Buckets {
+    /// Unset if rate limiting is disabled.
+    limits: Option<Limits>,
+    buckets: HashMap<(usize, RateLimitKey), Bucket>,
+    capacity: usize,
+    pruned: Instant,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Limits {
+    default: Limit,
+    /// Sorted by descending prefix length so that the longest prefix matches first.
+    routes: Vec<RouteLimit>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Outcome of taking a token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Decision {
+    limit: Limit,
+    remaining: u32,
+    /// Time until the bucket is full again.
+    reset: Duration,
+    /// Time until a token is available if the request was rejected.
+    retry_after: Option<Duration>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(settings: Option<&RateLimitSettings>, metrics: Arc<Metrics>) -> Self {
+        RateLimiter {
+            buckets: Mutex::new(Buckets {
+                limits: settings.map(Limits::new),
+                buckets: HashMap::new(),
+                capacity: MAX_BUCKETS,
+                pruned: Instant::now(),
+            }),
+            metrics,
+        }
+    }
+
+    /// Apply new limits to requests that start afterwards.
+    ///
+    /// Clients start with full buckets if the limits changed.
+    pub(crate) fn reload(&self, settings: Option<&RateLimitSettings>) {
+        let limits = settings.map(Limits::new);
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if buckets.limits != limits {
+            buckets.limits = limits;
+            buckets.buckets.clear();
+        }
+    }
+
+    /// Take a token from `key`'s bucket for `path`'s group.
+    ///
+    /// Returns `None` if rate limiting is disabled.
+    fn check(&self, key: RateLimitKey, path: &str, now: Instant) -> Option<Decision> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if now.saturating_duration_since(buckets.pruned) >= PRUNE_INTERVAL {
+            buckets.prune(now);
+        }
+
+        let (group, limit) = buckets.limits.as_ref()?.group(path);
+        let key = (group, key);
+        if !buckets.buckets.contains_key(&key) && buckets.buckets.len() >= buckets.capacity {
+            buckets.make_room(now);
+        }
+
+        let bucket = buckets.buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: limit.burst.get().into(),
+            updated: now,
+        });
+        let decision = bucket.take(limit, now);
+
+        if decision.retry_after.is_some() {
+            let label = buckets.limits.as_ref()?.label(group);
+            self.metrics.observe_rate_limited(label);
+        }
+        Some(decision)
+    }
+}
+
+impl Limits {
+    fn new(settings: &RateLimitSettings) -> Self {
+        let mut routes = settings.routes.clone();
+        routes.sort_by_key(|route| std::cmp::Reverse(route.prefix.len()));
+
+        Limits {
+            default: settings.limit,
+            routes,
+        }
+    }
+
+    // Group index and limit for `path`. The default group is 0.
+    fn group(&self, path: &str) -> (usize, Limit) {
+        self.routes
+            .iter()
+            .enumerate()
+            .find(|(_, route)| matches_prefix(path, &route.prefix))
+            .map(|(i, route)| (i + 1, route.limit))
+            .unwrap_or((0, self.default))
+    }
+
+    fn label(&self, group: usize) -> &str {
+        match group {
+            0 => DEFAULT_GROUP,
+            i => &self.routes[i - 1].prefix,
+        }
+    }
+
+    fn limit(&self, group: usize) -> Limit {
+        match group {
+            0 => self.default,
+            i => self.routes[i - 1].limit,
+        }
+    }
+}
+
+impl Buckets {
+    // Full buckets behave the same as new ones, so they're dropped to bound memory
+    fn prune(&mut self, now: Instant) {
+        match &self.limits {
+            Some(limits) => self.buckets.retain(|(group, _), bucket| {
+                let limit = limits.limit(*group);
+                // Left alone so that `updated` is still the last time the client was seen
+                bucket.tokens_at(limit, now) < limit.burst.get().into()
+            }),
+            None => self.buckets.clear(),
+        }
+        self.pruned = now;
+    }
+
+    // Prune early, and drop the least recently used tenth of the buckets if that isn't enough so
+    // that clients with many addresses can't make every request scan the map
+    fn make_room(&mut self, now: Instant) {
+        self.prune(now);
+        if self.buckets.len() < self.capacity {
+            return;
+        }
+
+        // Buckets updated at the same instant are dropped in no particular order
+        let count = (self.capacity / 10).clamp(1, self.buckets.len());
+        let mut updated: Vec<(Instant, (usize, RateLimitKey))> = self
+            .buckets
+            .iter()
+            .map(|(key, bucket)| (bucket.updated, key.clone()))
+            .collect();
+        updated.select_nth_unstable_by_key(count - 1, |&(updated, _)| updated);
+        for (_, key) in &updated[..count] {
+            self.buckets.remove(key);
+        }
+        warn!(
+            "More than {} clients are rate limited; dropped the least recently used buckets",
+            self.capacity
+        );
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: Limit, now: Instant) {
+        self.tokens = self.tokens_at(limit, now);
+        self.updated = now;
+    }
+
+    // Tokens in the bucket at `now` if it isn't used until then
+    fn tokens_at(&self, limit: Limit, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+
+        (self.tokens + elapsed * limit.per_second()).min(limit.burst.get().into())
+    }
+
+    fn take(&mut self, limit: Limit, now: Instant) -> Decision {
+        self.refill(limit, now);
+
+        let retry_after = if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(limit.time_for(1.0 - self.tokens))
+        };
+
+        Decision {
+            limit,
+            remaining: self.tokens as u32,
+            reset: limit.time_for(f64::from(limit.burst.get()) - self.tokens),
+            retry_after,
+        }
+    }
+}
+
+impl Limit {
+    fn per_second(&self) -> f64 {
+        f64::from(self.requests_per_minute.get()) / 60.0
+    }
+
+    // Time to refill `tokens`
+    fn time_for(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64(tokens.max(0.0) / self.per_second())
+    }
+}
+
+impl Decision {
+    // `RateLimit-*` fields from the IETF draft on rate limit headers
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RATELIMIT_LIMIT, self.limit.burst.get().into());
+        headers.insert(RATELIMIT_REMAINING, self.remaining.into());
+        headers.insert(RATELIMIT_RESET, seconds(self.reset).into());
+
+        if let Some(retry_after) = self.retry_after {
+            headers.insert(header::RETRY_AFTER, seconds(retry_after).into());
+        }
+        headers
+    }
+}
+
+/// Reject requests from clients that exceeded their limit.
+pub(crate) async fn limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = request
+        .extensions()
+        .get::<RateLimitKey>()
+        .cloned()
+        .or_else(|| match request.extensions().get::<ClientIp>()? {
+            &ClientIp::Ip(ip) => Some(RateLimitKey::ip(ip)),
+            // Local clients would all share a bucket
+            ClientIp::Local => None,
+        });
+    let Some(key) = key else {
+        return next.run(request).await;
+    };
+
+    let Some(decision) = limiter.check(key, request.uri().path(), Instant::now()) else {
+        return next.run(request).await;
+    };
+    if decision.retry_after.is_some() {
+        debug!("Rate limited request for {}", request.uri().path());
+        return (
+            decision.headers(),
+            Problem::new(StatusCode::TOO_MANY_REQUESTS).render_for(request.headers()),
+        )
+            .into_response();
+    }
+
+    let mut response = next.run(request).await;
+    response.headers_mut().extend(decision.headers());
+    response
+}
+
+// `/auth` matches `/auth` and `/auth/login` but not `/authors`
+fn matches_prefix(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'),
+        None => false,
+    }
+}
+
+// Whole seconds rounded up so that clients don't retry too early
+fn seconds(duration: Duration) -> u64 {
+    let seconds = duration.as_secs();
+
+    if duration.subsec_nanos() > 0 {
+        seconds + 1
+    } else {
+        seconds
+    }
+}
+
+impl RateLimitKey {
+    /// Key for a client's address. IPv6 addresses are truncated to their `/64` network.
+    pub fn ip(ip: IpAddr) -> Self {
+        match ip.to_canonical() {
+            IpAddr::V6(ip) => {
+                let network = ip.to_bits() & (u128::MAX << 64);
+                RateLimitKey::Ip(Ipv6Addr::from_bits(network).into())
+            }
+            ip => RateLimitKey::Ip(ip),
+        }
+    }
+}
+
+impl Display for RateLimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitKey::Ip(ip) => write!(f, "{ip}"),
+            // Tokens are secrets
+            RateLimitKey::Identity(_) => write!(f, "(identity)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Ipv4Addr,
+        num::NonZeroU32,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        matches_prefix, Limit, RateLimitKey, RateLimitSettings, RateLimiter, RouteLimit,
+        DEFAULT_GROUP,
+    };
+    use crate::metrics::{GroupLabels, Metrics};
+
+    // Rejections counted for `group`, if any
+    fn rate_limited(metrics: &Metrics, group: &str) -> Option<u64> {
+        let labels = GroupLabels {
+            group: group.to_owned(),
+        };
+        metrics
+            .rate_limited
+            .get(&labels)
+            .map(|counter| counter.get())
+    }
+
+    fn limit(burst: u32, requests_per_minute: u32) -> Limit {
+        Limit {
+            burst: NonZeroU32::new(burst).unwrap(),
+            requests_per_minute: NonZeroU32::new(requests_per_minute).unwrap(),
+        }
+    }
+
+    fn limiter() -> RateLimiter {
+        let settings = RateLimitSettings {
+            limit: limit(2, 60),
+            routes: vec![RouteLimit {
+                prefix: "/auth".into(),
+                limit: limit(1, 1),
+            }],
+        };
+
+        RateLimiter::new(Some(&settings), Arc::default())
+    }
+
+    fn client(last: u8) -> RateLimitKey {
+        RateLimitKey::Ip(Ipv4Addr::new(192, 0, 2, last).into())
+    }
+
+    #[test]
+    fn empty_buckets_reject_until_refilled() {
+        let limiter = limiter();
+        let start = Instant::now();
+
+        let first = limiter.check(client(1), "/", start).unwrap();
+        assert_eq!((None, 1), (first.retry_after, first.remaining));
+        assert_eq!(Duration::from_secs(1), first.reset);
+        assert_eq!(
+            None,
+            limiter.check(client(1), "/", start).unwrap().retry_after
+        );
+
+        let rejected = limiter.check(client(1), "/", start).unwrap();
+        assert_eq!(Some(Duration::from_secs(1)), rejected.retry_after);
+        assert_eq!(Some(1), rate_limited(&limiter.metrics, DEFAULT_GROUP));
+
+        let later = start + Duration::from_secs(1);
+        assert_eq!(
+            None,
+            limiter.check(client(1), "/", later).unwrap().retry_after
+        );
+    }
+
+    #[test]
+    fn clients_and_groups_have_their_own_buckets() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(
+            None,
+            limiter
+                .check(client(1), "/auth/login", now)
+                .unwrap()
+                .retry_after
+        );
+        assert_eq!(
+            Some(Duration::from_secs(60)),
+            limiter.check(client(1), "/auth", now).unwrap().retry_after
+        );
+        assert_eq!(
+            None,
+            limiter.check(client(2), "/auth", now).unwrap().retry_after
+        );
+        assert_eq!(
+            None,
+            limiter.check(client(1), "/", now).unwrap().retry_after
+        );
+
+        assert_eq!(Some(1), rate_limited(&limiter.metrics, "/auth"));
+        assert_eq!(None, rate_limited(&limiter.metrics, DEFAULT_GROUP));
+    }
+
+    #[test]
+    fn full_buckets_are_pruned() {
+        let limiter = limiter();
+        let now = Instant::now();
+        limiter.check(client(1), "/", now);
+        limiter.check(client(2), "/auth", now + Duration::from_secs(30));
+
+        // The default bucket refills within a second but `/auth` takes a minute
+        limiter.check(client(3), "/", now + Duration::from_secs(61));
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(2, buckets.buckets.len());
+        assert!(!buckets.buckets.contains_key(&(0, client(1))));
+    }
+
+    #[test]
+    fn reloading_applies_new_limits() {
+        let limiter = limiter();
+        let now = Instant::now();
+        limiter.check(client(1), "/auth", now);
+
+        // Unchanged limits keep the buckets
+        limiter.reload(Some(&RateLimitSettings {
+            limit: limit(2, 60),
+            routes: vec![RouteLimit {
+                prefix: "/auth".into(),
+                limit: limit(1, 1),
+            }],
+        }));
+        assert!(limiter
+            .check(client(1), "/auth", now)
+            .unwrap()
+            .retry_after
+            .is_some());
+
+        limiter.reload(Some(&RateLimitSettings {
+            limit: limit(5, 60),
+            routes: Vec::new(),
+        }));
+        let decision = limiter.check(client(1), "/auth", now).unwrap();
+        assert_eq!((None, 4), (decision.retry_after, decision.remaining));
+
+        limiter.reload(None);
+        assert!(limiter.check(client(1), "/auth", now).is_none());
+    }
+
+    #[test]
+    fn ipv6_clients_are_limited_per_network() {
+        let ip = |ip: &str| RateLimitKey::ip(ip.parse().unwrap());
+
+        assert_eq!(ip("2001:db8:1:2::"), ip("2001:db8:1:2:aaaa:bbbb:cccc:dddd"));
+        assert_ne!(ip("2001:db8:1:2::"), ip("2001:db8:1:3::"));
+        assert_eq!(ip("192.0.2.1"), ip("::ffff:192.0.2.1"));
+        assert_ne!(ip("192.0.2.1"), ip("192.0.2.2"));
+    }
+
+    #[test]
+    fn least_recently_used_buckets_are_dropped_when_full() {
+        let limiter = limiter();
+        limiter.buckets.lock().unwrap().capacity = 10;
+        let now = Instant::now();
+
+        // Every client is rejected once, so none of the buckets are full
+        for i in 0..10 {
+            let later = now + Duration::from_millis(i.into());
+            limiter.check(client(i), "/auth", later);
+            limiter.check(client(i), "/auth", later);
+        }
+        limiter.check(client(10), "/auth", now + Duration::from_millis(10));
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(10, buckets.buckets.len());
+        assert!(!buckets.buckets.contains_key(&(1, client(0))));
+        assert!(buckets.buckets.contains_key(&(1, client(1))));
+        assert!(buckets.buckets.contains_key(&(1, client(10))));
+    }
+
+    #[test]
+    fn a_tenth_of_the_buckets_are_dropped_if_updated_together() {
+        let limiter = limiter();
+        limiter.buckets.lock().unwrap().capacity = 20;
+        let now = Instant::now();
+
+        for i in 0..20 {
+            limiter.check(client(i), "/auth", now);
+            limiter.check(client(i), "/auth", now);
+        }
+        limiter.check(client(20), "/auth", now);
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(19, buckets.buckets.len());
+        assert!(buckets.buckets.contains_key(&(1, client(20))));
+    }
+
+    #[test]
+    fn prefixes_match_whole_segments() {
+        assert!(matches_prefix("/auth", "/auth"));
+        assert!(matches_prefix("/auth/login", "/auth"));
+        assert!(matches_prefix("/auth/login", "/auth/"));
+        assert!(!matches_prefix("/authors", "/auth"));
+        assert!(!matches_prefix("/", "/auth"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Records request counts, latencies, and in-flight requests for `/metrics`.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use tokio::time::Instant;
+
+use crate::metrics::{Metrics, RequestLabels};
+
+/// Route label of requests that didn't match a route. Their paths aren't used as labels so that
+/// scanners can't create unbounded series.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Record the latency of each request by its matched route.
+///
+/// Requests are counted when the response's headers are ready rather than after the body is sent.
+pub(crate) async fn record(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE, MatchedPath::as_str)
+        .to_owned();
+    let method = method_label(request.method()).to_owned();
+
+    let start = Instant::now();
+    let in_flight = InFlight::start(&metrics);
+    let response = next.run(request).await;
+    drop(in_flight);
+
+    metrics.observe_request(
+        RequestLabels {
+            route,
+            method,
+            status: response.status().as_u16(),
+        },
+        start.elapsed(),
+    );
+    response
+}
+
+// Nonstandard methods share a label for the same reason as unmatched routes
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => "other",
+    }
+}
+
+// Decrements the gauge even if the client disconnects and the request is dropped
+struct InFlight<'a>(&'a Metrics);
+
+impl<'a> InFlight<'a> {
+    fn start(metrics: &'a Metrics) -> Self {
+        metrics.in_flight.inc();
+        InFlight(metrics)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.dec();
+    }
+}
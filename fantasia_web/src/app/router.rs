@@ -1,45 +1,210 @@
-use std::time::Duration;
+use std::sync::Arc;
 
 use axum::{
-    routing::{get, post},
+    extract::{ConnectInfo, DefaultBodyLimit, Request},
+    routing::get,
     Router,
 };
-use tower::ServiceBuilder;
+use tower::{Layer, ServiceBuilder};
 use tower_http::{
-    compression::CompressionLayer,
-    decompression::DecompressionLayer,
-    normalize_path::NormalizePathLayer,
-    request_id::MakeRequestUuid,
-    timeout::TimeoutLayer,
-    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
-    ServiceBuilderExt,
+    cors::CorsLayer, decompression::RequestDecompressionLayer, normalize_path::NormalizePathLayer,
+    request_id::MakeRequestUuid, trace::TraceLayer, ServiceBuilderExt,
 };
 
+use super::{
+    compression::{choose_encoding, Encoding},
+    cors::CorsSettings,
+    http::{HttpSettings, NormalizePath},
+    limits::{self, ReloadableLimits},
+    panic::PanicResponse,
+    rate_limit::{self, RateLimiter},
+    request_metrics,
+    security_headers::{self, SecurityHeaderValues},
+    trace::RedactingTrace,
+    PeerAddr,
+};
 use crate::{
-    routes::{fallback_404, health_check, index},
+    error::problem,
+    extract::{request_id, TrustedProxies},
+    metrics::Metrics,
+    routes::{
+        fallback_404, health_check, index, live, method_not_allowed, pool_stats,
+        prometheus_metrics, ready,
+    },
     state::State,
 }; //sql_temp};
 
-pub fn bind_routes(state: State) -> Router {
-    Router::new()
+/// Routes served on [super::Role::Public] listeners.
+///
+/// `/metrics` is only added if `serve_metrics` is set, which is the case if there aren't any
+/// [super::Role::Admin] listeners. Requests are limited by the current `limits`.
+pub fn bind_routes(
+    state: State,
+    http: &HttpSettings,
+    limits: Arc<ReloadableLimits>,
+    trusted_proxies: TrustedProxies,
+    cors: Option<&CorsSettings>,
+    serve_metrics: bool,
+) -> Router {
+    let public = PublicMiddleware {
+        limiter: Some(limits.rate_limiter.clone()),
+        cors: cors.map(CorsSettings::layer),
+    };
+    let metrics = state.metrics.clone();
+
+    let mut router = Router::new()
         .route("/", get(index))
         .route("/health_check", get(health_check))
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready));
+    if serve_metrics {
+        router = router.route("/metrics", get(prometheus_metrics));
+    }
+    let router = router.with_state(state);
+
+    with_middleware(router, http, limits, trusted_proxies, metrics, public)
+}
+
+/// Operational routes served only on [super::Role::Admin] listeners.
+///
+/// `extra` is merged with Fantasia's own admin routes, such as `/pool` and `/metrics`.
+pub fn bind_admin_routes(
+    state: State,
+    extra: Router,
+    http: &HttpSettings,
+    limits: Arc<ReloadableLimits>,
+    trusted_proxies: TrustedProxies,
+) -> Router {
+    let metrics = state.metrics.clone();
+    let router = Router::new()
+        .route("/health_check", get(health_check))
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready))
+        .route("/pool", get(pool_stats))
+        .route("/metrics", get(prometheus_metrics))
+        .with_state(state)
+        .merge(extra);
+
+    // Admin listeners aren't exposed to browsers or clients, so they aren't rate limited
+    with_middleware(
+        router,
+        http,
+        limits,
+        trusted_proxies,
+        metrics,
+        PublicMiddleware::default(),
+    )
+}
+
+// Middleware that only applies to public routes
+#[derive(Default)]
+struct PublicMiddleware {
+    limiter: Option<Arc<RateLimiter>>,
+    cors: Option<CorsLayer>,
+}
+
+// Middleware shared by the public and admin routers
+fn with_middleware(
+    router: Router,
+    http: &HttpSettings,
+    limits: Arc<ReloadableLimits>,
+    trusted_proxies: TrustedProxies,
+    metrics: Arc<Metrics>,
+    public: PublicMiddleware,
+) -> Router {
+    let trusted_proxies = Arc::new(trusted_proxies);
+    let trace = RedactingTrace::new(http.redact_headers.clone());
+    let compression = &http.compression;
+
+    let mut router = router
         .fallback(fallback_404)
+        .method_not_allowed_fallback(method_not_allowed)
+        // Responds with a problem, so it has to be inside `negotiate`
+        .layer(PanicResponse::layer(metrics.clone()))
+        // Rewrites problem bodies, so it has to run before compression
+        .layer(axum::middleware::from_fn(problem::negotiate))
+        // Tells the compression layers which of them should compress the response
+        .layer(axum::middleware::from_fn_with_state(
+            compression.clone(),
+            choose_encoding,
+        ))
         .layer(
             ServiceBuilder::new()
-                .set_x_request_id(MakeRequestUuid)
-                .layer(NormalizePathLayer::trim_trailing_slash())
+                // Request bodies. Unsupported encodings are rejected with `415 Unsupported Media Type`
                 .layer(
-                    TraceLayer::new_for_http()
-                        .make_span_with(DefaultMakeSpan::new().include_headers(true))
-                        .on_response(DefaultOnResponse::new().include_headers(true)),
+                    RequestDecompressionLayer::new()
+                        .gzip(compression.gzip)
+                        .deflate(compression.deflate)
+                        .br(compression.br),
                 )
-                .layer(TimeoutLayer::new(Duration::from_secs(30)))
-                // Defaults to true for each enabled compression algo
-                // https://github.com/tower-rs/tower-http/blob/6f964b12fd059a87feb8042cc82cdc8af69cb0b8/tower-http/src/compression_utils.rs#L120-L129
-                .layer(DecompressionLayer::new())
-                .layer(CompressionLayer::new())
-                .propagate_x_request_id(),
+                // One layer per algorithm so that each has its own level
+                .layer(compression.layer(Encoding::Br))
+                .layer(compression.layer(Encoding::Gzip))
+                .layer(compression.layer(Encoding::Deflate))
+                .propagate_x_request_id()
+                .layer(DefaultBodyLimit::max(http.body_limit)),
         )
-        .with_state(state)
+        // Reads the timeout for each request so that reloading applies without rebuilding
+        .layer(axum::middleware::from_fn_with_state(
+            limits,
+            limits::timeout,
+        ));
+
+    // Inside tracing so that rejected requests are logged with the client's address
+    if let Some(limiter) = public.limiter {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            limiter,
+            rate_limit::limit,
+        ));
+    }
+
+    // Outside rate limiting so that preflights aren't counted and browsers can read 429s
+    if let Some(cors) = public.cors {
+        router = router.layer(cors);
+    }
+
+    // Every response including errors from the middleware above, such as timeouts
+    let router = router
+        .layer(axum::middleware::from_fn_with_state(
+            SecurityHeaderValues::new(&http.security_headers),
+            security_headers::set_headers,
+        ))
+        // `Router::layer` wraps each route, so the matched route is known. Outside the other
+        // middleware so that rejected requests are counted too
+        .layer(axum::middleware::from_fn_with_state(
+            metrics,
+            request_metrics::record,
+        ));
+
+    let router = router.layer(
+        ServiceBuilder::new()
+            // Clients may send their own ID, but only valid ones are kept
+            .map_request(request_id::drop_invalid)
+            .set_x_request_id(MakeRequestUuid)
+            // Resolved before tracing so that spans and handlers agree on the client
+            .map_request(move |request| resolve_client(&trusted_proxies, request))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(trace.clone())
+                    .on_response(trace),
+            ),
+    );
+
+    // Middleware added with `Router::layer` runs after routing, so the path has to be rewritten by
+    // a service in front of the router instead
+    match http.normalize_path {
+        NormalizePath::TrimTrailingSlash => {
+            Router::new().fallback_service(NormalizePathLayer::trim_trailing_slash().layer(router))
+        }
+        NormalizePath::None => router,
+    }
+}
+
+fn resolve_client(trusted_proxies: &TrustedProxies, mut request: Request) -> Request {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<PeerAddr>>() {
+        let client = trusted_proxies.resolve(peer, request.headers());
+        request.extensions_mut().insert(client);
+    }
+
+    request
 }
@@ -0,0 +1,132 @@
+//! Security headers added to every response, e.g. `[fantasia.http.security_headers]`.
+//!
+//! Headers that a route sets itself are left alone so that individual routes may relax or tighten
+//! the defaults.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Deserializer};
+
+use super::tls::TlsConnection;
+
+/// Values of the security headers. An empty string in config omits the header.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct SecurityHeaders {
+    /// `Strict-Transport-Security`, which is only sent on TLS connections.
+    #[serde(deserialize_with = "deserialize_header")]
+    pub strict_transport_security: Option<HeaderValue>,
+    #[serde(deserialize_with = "deserialize_header")]
+    pub content_security_policy: Option<HeaderValue>,
+    #[serde(deserialize_with = "deserialize_header")]
+    pub x_content_type_options: Option<HeaderValue>,
+    #[serde(deserialize_with = "deserialize_header")]
+    pub referrer_policy: Option<HeaderValue>,
+    #[serde(deserialize_with = "deserialize_header")]
+    pub permissions_policy: Option<HeaderValue>,
+    #[serde(deserialize_with = "deserialize_header")]
+    pub x_frame_options: Option<HeaderValue>,
+}
+
+impl Default for SecurityHeaders {
+    // Fantasia serves an API rather than documents, so nothing may be loaded, framed, or sniffed
+    fn default() -> Self {
+        SecurityHeaders {
+            strict_transport_security: Some(HeaderValue::from_static(
+                "max-age=31536000; includeSubDomains",
+            )),
+            content_security_policy: Some(HeaderValue::from_static(
+                "default-src 'none'; frame-ancestors 'none'",
+            )),
+            x_content_type_options: Some(HeaderValue::from_static("nosniff")),
+            referrer_policy: Some(HeaderValue::from_static("no-referrer")),
+            permissions_policy: Some(HeaderValue::from_static(
+                "camera=(), geolocation=(), microphone=()",
+            )),
+            x_frame_options: Some(HeaderValue::from_static("DENY")),
+        }
+    }
+}
+
+/// [SecurityHeaders] resolved for [set_headers].
+#[derive(Debug, Clone)]
+pub(crate) struct SecurityHeaderValues {
+    /// Headers for every response.
+    always: Vec<(HeaderName, HeaderValue)>,
+    /// `Strict-Transport-Security` for TLS connections.
+    hsts: Option<HeaderValue>,
+}
+
+impl SecurityHeaderValues {
+    pub(crate) fn new(settings: &SecurityHeaders) -> Arc<Self> {
+        let always = [
+            (
+                header::CONTENT_SECURITY_POLICY,
+                &settings.content_security_policy,
+            ),
+            (
+                header::X_CONTENT_TYPE_OPTIONS,
+                &settings.x_content_type_options,
+            ),
+            (header::REFERRER_POLICY, &settings.referrer_policy),
+            (
+                HeaderName::from_static("permissions-policy"),
+                &settings.permissions_policy,
+            ),
+            (header::X_FRAME_OPTIONS, &settings.x_frame_options),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.clone()?)))
+        .collect();
+
+        Arc::new(SecurityHeaderValues {
+            always,
+            hsts: settings.strict_transport_security.clone(),
+        })
+    }
+}
+
+/// Add security headers that the response doesn't already have.
+pub(crate) async fn set_headers(
+    State(values): State<Arc<SecurityHeaderValues>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // HSTS over plain HTTP could be injected by an attacker, so browsers ignore it anyway
+    let tls = request.extensions().get::<TlsConnection>().is_some();
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    for (name, value) in &values.always {
+        if !headers.contains_key(name) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+    if let (true, Some(hsts)) = (tls, &values.hsts) {
+        if !headers.contains_key(header::STRICT_TRANSPORT_SECURITY) {
+            headers.insert(header::STRICT_TRANSPORT_SECURITY, hsts.clone());
+        }
+    }
+
+    response
+}
+
+fn deserialize_header<'de, D>(deserializer: D) -> Result<Option<HeaderValue>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    HeaderValue::try_from(value)
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom("Invalid header value"))
+}
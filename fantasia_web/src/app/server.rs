@@ -0,0 +1,230 @@
+//! Accept loop shared by every [super::Fantasia] listener.
+//!
+//! [axum::serve] only handles plain TCP, so Fantasia drives [hyper] connections itself. This
+//! allows terminating TLS and serving Unix domain sockets before handing a connection to the
+//! [Router]. Listeners behind a load balancer may also read the client's address from a PROXY
+//! protocol header first.
+
+use std::{error::Error, future::Future, io, time::Duration};
+
+use axum::{extract::ConnectInfo, Router};
+use hyper::{body::Incoming, service::service_fn, Request};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{
+        conn::auto::Builder,
+        graceful::{GracefulShutdown, Watcher},
+    },
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream, UnixListener, UnixStream},
+    task::JoinSet,
+    time,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tokio_util::either::Either;
+use tower::ServiceExt;
+use tracing::{debug, error, info, trace};
+
+use super::{addr::PeerAddr, proxy, shutdown::Shutdown, tls::TlsConnection};
+
+/// Time a client has to send its PROXY header and finish the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Listener that yields connections for [serve].
+pub(crate) trait Accept: Send + 'static {
+    type Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Stream, PeerAddr)>> + Send;
+}
+
+impl Accept for TcpListener {
+    type Stream = TcpStream;
+
+    async fn accept(&self) -> io::Result<(Self::Stream, PeerAddr)> {
+        let (stream, addr) = TcpListener::accept(self).await?;
+        Ok((stream, PeerAddr::Tcp(addr)))
+    }
+}
+
+impl Accept for UnixListener {
+    type Stream = UnixStream;
+
+    async fn accept(&self) -> io::Result<(Self::Stream, PeerAddr)> {
+        let (stream, addr) = UnixListener::accept(self).await?;
+        Ok((
+            stream,
+            PeerAddr::Unix(addr.as_pathname().map(ToOwned::to_owned)),
+        ))
+    }
+}
+
+/// Accept connections on `listener` until `shutdown` is triggered.
+///
+/// With `proxy_protocol`, each connection must start with a PROXY header whose client address
+/// replaces the peer's address. After shutdown, the listener is closed and open connections are
+/// asked to finish their in-flight requests. The returned future resolves once every connection
+/// is closed.
+pub(crate) async fn serve<L>(
+    listener: L,
+    router: Router,
+    tls: Option<TlsAcceptor>,
+    proxy_protocol: bool,
+    shutdown: Shutdown,
+) -> io::Result<()>
+where
+    L: Accept,
+{
+    let builder = Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    let mut connections = JoinSet::new();
+
+    let signal = shutdown.triggered();
+    tokio::pin!(signal);
+
+    loop {
+        let (stream, remote_addr) = tokio::select! {
+            conn = listener.accept() => match conn {
+                Ok(conn) => conn,
+                Err(e) => {
+                    accept_error(e).await;
+                    continue;
+                }
+            },
+            // Reap finished connections so the set doesn't grow unbounded.
+            Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+            _ = &mut signal => break,
+        };
+        trace!("Accepted connection from {remote_addr}");
+
+        let builder = builder.clone();
+        let watcher = graceful.watcher();
+        let router = router.clone();
+        let tls = tls.clone();
+        let shutdown = shutdown.clone();
+
+        connections.spawn(async move {
+            // Clients that stall before HTTP would otherwise hold the task through a shutdown
+            let handshake = handshake(stream, remote_addr.clone(), tls, proxy_protocol);
+            let (stream, remote_addr, tls) = tokio::select! {
+                result = time::timeout(HANDSHAKE_TIMEOUT, handshake) => match result {
+                    Ok(Some(connection)) => connection,
+                    Ok(None) => return,
+                    Err(_) => {
+                        debug!("Handshake with {remote_addr} timed out");
+                        return;
+                    }
+                },
+                _ = shutdown.triggered() => return,
+            };
+
+            let result =
+                serve_connection(stream, remote_addr.clone(), tls, router, &builder, watcher).await;
+            if let Err(e) = result {
+                debug!("Connection from {remote_addr} closed with an error: {e}");
+            }
+        });
+    }
+
+    // Stop accepting before draining so that new clients are refused immediately.
+    drop(listener);
+    info!(
+        "Listener closed; waiting on {} connections",
+        connections.len()
+    );
+
+    graceful.shutdown().await;
+    while connections.join_next().await.is_some() {}
+
+    Ok(())
+}
+
+/// Read the PROXY header and complete the TLS handshake if the listener expects them.
+///
+/// Returns the stream to serve HTTP on, the client's address, and whether it's TLS, or `None` if
+/// the client should be disconnected.
+async fn handshake<S>(
+    mut stream: S,
+    mut remote_addr: PeerAddr,
+    tls: Option<TlsAcceptor>,
+    proxy_protocol: bool,
+) -> Option<(Either<TlsStream<S>, S>, PeerAddr, bool)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if proxy_protocol {
+        match proxy::read_header(&mut stream).await {
+            Ok(Some(client)) => {
+                trace!("{remote_addr} proxied a connection from {client}");
+                remote_addr = PeerAddr::Tcp(client);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                debug!("Rejected connection from {remote_addr} without a PROXY header: {e}");
+                return None;
+            }
+        }
+    }
+
+    match tls {
+        Some(acceptor) => match acceptor.accept(stream).await {
+            Ok(stream) => Some((Either::Left(stream), remote_addr, true)),
+            Err(e) => {
+                debug!("TLS handshake with {remote_addr} failed: {e}");
+                None
+            }
+        },
+        None => Some((Either::Right(stream), remote_addr, false)),
+    }
+}
+
+/// Serve HTTP/1.1 or HTTP/2 on a single connection.
+///
+/// The remote address is attached to each request so that handlers may extract it with
+/// [ConnectInfo]. Requests on TLS connections are also marked with [TlsConnection].
+async fn serve_connection<I>(
+    io: I,
+    remote_addr: PeerAddr,
+    tls: bool,
+    router: Router,
+    builder: &Builder<TokioExecutor>,
+    watcher: Watcher,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let service = service_fn(move |mut request: Request<Incoming>| {
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(remote_addr.clone()));
+        if tls {
+            request.extensions_mut().insert(TlsConnection);
+        }
+        router.clone().oneshot(request)
+    });
+
+    watcher
+        .watch(builder.serve_connection_with_upgrades(TokioIo::new(io), service))
+        .await
+}
+
+// Errors on individual connections (e.g. a reset before `accept`) are harmless. Anything else,
+// such as running out of file descriptors, is retried after a short delay.
+async fn accept_error(e: io::Error) {
+    if is_connection_error(&e) {
+        return;
+    }
+
+    error!("Accept error: {e}");
+    time::sleep(Duration::from_secs(1)).await;
+}
+
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
@@ -0,0 +1,63 @@
+use std::{future::Future, io, time::Duration};
+
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Default time to wait for in-flight requests after a shutdown is requested.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Handle used to stop every [super::Fantasia] listener spawned from the same builder.
+///
+/// Cloning the handle is cheap; every clone triggers the same shutdown.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every listener to stop accepting connections and drain in-flight requests.
+    pub fn trigger(&self) {
+        info!("Shutdown requested");
+        self.token.cancel();
+    }
+
+    /// Whether a shutdown was already requested.
+    pub fn is_triggered(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once a shutdown is requested.
+    pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.token.clone().cancelled_owned()
+    }
+}
+
+/// Drive `server` until it finishes or until a shutdown is requested and `grace` elapses.
+///
+/// `server` is expected to stop accepting connections on its own once [Shutdown::triggered]
+/// resolves. Connections that are still open after `grace` are dropped.
+pub(crate) async fn drain<F>(server: F, shutdown: Shutdown, grace: Duration) -> io::Result<()>
+where
+    F: Future<Output = io::Result<()>>,
+{
+    tokio::pin!(server);
+
+    tokio::select! {
+        res = &mut server => res,
+        _ = shutdown.triggered() => {
+            info!("Draining connections for up to {grace:?}");
+            match time::timeout(grace, server).await {
+                Ok(res) => res,
+                Err(_) => {
+                    warn!("Grace period elapsed; dropping remaining connections");
+                    Ok(())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! TLS termination with [rustls].
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use serde::Deserialize;
+use tokio_rustls::{
+    rustls::{
+        crypto::ring,
+        pki_types::{CertificateDer, PrivateKeyDer},
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+        version::{TLS12, TLS13},
+        ServerConfig, SupportedProtocolVersion,
+    },
+    TlsAcceptor,
+};
+use tracing::info;
+
+/// ALPN protocols advertised by Fantasia in order of preference.
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];
+
+static TLS12_AND_UP: [&SupportedProtocolVersion; 2] = [&TLS13, &TLS12];
+static TLS13_ONLY: [&SupportedProtocolVersion; 1] = [&TLS13];
+
+/// TLS settings for HTTPS listeners.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TlsSettings {
+    /// PEM encoded certificate chain starting with the end entity certificate.
+    pub cert_chain: PathBuf,
+    /// PEM encoded private key for the end entity certificate.
+    pub key: PathBuf,
+    /// Oldest TLS version accepted from clients.
+    #[serde(default)]
+    pub min_version: TlsVersion,
+}
+
+/// TLS protocol versions supported by [rustls].
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TlsVersion {
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// Certificate chain and key that may be replaced while Fantasia is running.
+///
+/// New handshakes use the latest pair while established connections keep theirs.
+#[derive(Debug)]
+pub struct ReloadableCert {
+    cert_chain: PathBuf,
+    key: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+/// Request extension for requests received over TLS.
+///
+/// Requests forwarded by a proxy that terminated TLS don't have it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsConnection;
+
+impl TlsVersion {
+    /// Protocol versions at or above this version.
+    fn protocol_versions(self) -> &'static [&'static SupportedProtocolVersion] {
+        match self {
+            TlsVersion::Tls12 => &TLS12_AND_UP,
+            TlsVersion::Tls13 => &TLS13_ONLY,
+        }
+    }
+}
+
+impl TlsSettings {
+    /// Load the certificate chain and key into a [rustls] server config.
+    ///
+    /// The config advertises HTTP/2 and HTTP/1.1 via ALPN.
+    pub fn server_config(&self) -> io::Result<ServerConfig> {
+        self.server_config_with(self.certificate()?)
+    }
+
+    /// Build an acceptor that terminates TLS on accepted connections.
+    pub fn acceptor(&self) -> io::Result<TlsAcceptor> {
+        self.reloadable_acceptor().map(|(acceptor, _)| acceptor)
+    }
+
+    /// Build an acceptor along with its certificate, which may be reloaded from the same files.
+    pub fn reloadable_acceptor(&self) -> io::Result<(TlsAcceptor, Arc<ReloadableCert>)> {
+        let cert = self.certificate()?;
+        let config = self.server_config_with(cert.clone())?;
+
+        Ok((TlsAcceptor::from(Arc::new(config)), cert))
+    }
+
+    /// Load the certificate chain and key.
+    #[tracing::instrument]
+    pub fn certificate(&self) -> io::Result<Arc<ReloadableCert>> {
+        let key = certified_key(&self.cert_chain, &self.key)?;
+        info!(
+            "Loaded TLS certificate from `{}`",
+            self.cert_chain.display()
+        );
+
+        Ok(Arc::new(ReloadableCert {
+            cert_chain: self.cert_chain.clone(),
+            key: self.key.clone(),
+            current: RwLock::new(Arc::new(key)),
+        }))
+    }
+
+    fn server_config_with(&self, cert: Arc<ReloadableCert>) -> io::Result<ServerConfig> {
+        let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_protocol_versions(self.min_version.protocol_versions())
+            .map_err(io::Error::other)?
+            .with_no_client_auth()
+            .with_cert_resolver(cert);
+        config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|proto| proto.to_vec()).collect();
+
+        Ok(config)
+    }
+}
+
+impl ReloadableCert {
+    /// Load the certificate chain and key again from the same files.
+    ///
+    /// The current pair is kept if the new pair can't be loaded or doesn't match.
+    #[tracing::instrument]
+    pub fn reload(&self) -> io::Result<()> {
+        let key = certified_key(&self.cert_chain, &self.key)?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(key);
+
+        info!(
+            "Reloaded TLS certificate from `{}`",
+            self.cert_chain.display()
+        );
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for ReloadableCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.current
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        )
+    }
+}
+
+// Load a certificate chain and the private key that matches its end entity certificate
+fn certified_key(cert_chain: &Path, key: &Path) -> io::Result<CertifiedKey> {
+    let certs = load_certs(cert_chain)?;
+    let key = load_key(key)?;
+
+    CertifiedKey::from_der(certs, key, &ring::default_provider())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = File::open(path).map(BufReader::new)?;
+    let certs = rustls_pemfile::certs(&mut reader).collect::<io::Result<Vec<_>>>()?;
+
+    if certs.is_empty() {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("No certificates found in `{}`", path.display()),
+        ))
+    } else {
+        Ok(certs)
+    }
+}
+
+fn load_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = File::open(path).map(BufReader::new)?;
+
+    rustls_pemfile::private_key(&mut reader)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("No private key found in `{}`", path.display()),
+        )
+    })
+}
@@ -0,0 +1,194 @@
+//! Request spans and response events for [tower_http::trace::TraceLayer].
+//!
+//! Headers are included to help debugging, but secrets such as `Authorization` and `Cookie` are
+//! replaced with `[redacted]` so that they never reach the logs.
+
+use std::{
+    fmt::{self, Debug},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderName, Response},
+};
+use serde::Deserialize;
+use tower_http::trace::{MakeSpan, OnResponse};
+use tracing::{debug, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{
+    extract::{request_id::REQUEST_ID_HEADER, ClientIp},
+    telemetry,
+};
+
+/// Placeholder for the values of redacted headers.
+const REDACTED: &str = "[redacted]";
+
+/// Headers whose values are redacted from spans and events.
+///
+/// Defaults to well-known headers that carry credentials. Setting the list replaces the defaults.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "Vec<String>")]
+pub struct RedactedHeaders(Vec<HeaderName>);
+
+/// Builds request spans and logs responses with [RedactedHeaders].
+#[derive(Debug, Clone)]
+pub(crate) struct RedactingTrace {
+    redacted: Arc<RedactedHeaders>,
+}
+
+/// [Debug] for a [HeaderMap] that hides redacted values.
+struct Redacted<'h> {
+    headers: &'h HeaderMap,
+    redacted: &'h RedactedHeaders,
+}
+
+impl RedactedHeaders {
+    pub fn new(names: Vec<HeaderName>) -> Self {
+        RedactedHeaders(names)
+    }
+
+    /// Whether `name`'s value is redacted.
+    pub fn contains(&self, name: &HeaderName) -> bool {
+        self.0.contains(name)
+    }
+}
+
+impl Default for RedactedHeaders {
+    fn default() -> Self {
+        RedactedHeaders(vec![
+            header::AUTHORIZATION,
+            header::PROXY_AUTHORIZATION,
+            header::COOKIE,
+            header::SET_COOKIE,
+            HeaderName::from_static("x-api-key"),
+            HeaderName::from_static("x-auth-token"),
+        ])
+    }
+}
+
+impl TryFrom<Vec<String>> for RedactedHeaders {
+    type Error = String;
+
+    fn try_from(names: Vec<String>) -> Result<Self, Self::Error> {
+        names
+            .iter()
+            .map(|name| {
+                HeaderName::try_from(name.as_str())
+                    .map_err(|_| format!("Invalid header name `{name}`"))
+            })
+            .collect::<Result<_, _>>()
+            .map(RedactedHeaders)
+    }
+}
+
+impl RedactingTrace {
+    pub(crate) fn new(redacted: RedactedHeaders) -> Self {
+        RedactingTrace {
+            redacted: Arc::new(redacted),
+        }
+    }
+
+    fn redact<'h>(&'h self, headers: &'h HeaderMap) -> Redacted<'h> {
+        Redacted {
+            headers,
+            redacted: &self.redacted,
+        }
+    }
+}
+
+// Same fields as `DefaultMakeSpan` with headers plus the resolved client address and request ID.
+// The span is a server span in the caller's trace if spans are exported. It's at `INFO` so that
+// release builds, which compile out `DEBUG`, still export it
+impl<B> MakeSpan<B> for RedactingTrace {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let span = tracing::info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            version = ?request.version(),
+            client_ip = request.extensions().get::<ClientIp>().map(tracing::field::display),
+            request_id = request
+                .headers()
+                .get(&REQUEST_ID_HEADER)
+                .and_then(|id| id.to_str().ok())
+                .map(tracing::field::display),
+            headers = ?self.redact(request.headers()),
+            otel.kind = "server",
+            otel.status_code = tracing::field::Empty,
+        );
+        span.set_parent(telemetry::extract(request.headers()));
+        span
+    }
+}
+
+// Same event as `DefaultOnResponse` with headers. Only server errors fail the exported span, so
+// errors that were logged and handled don't count
+impl<B> OnResponse<B> for RedactingTrace {
+    fn on_response(self, response: &Response<B>, latency: Duration, span: &Span) {
+        let status_code = if response.status().is_server_error() {
+            "error"
+        } else {
+            "unset"
+        };
+        span.record("otel.status_code", status_code);
+
+        debug!(
+            latency = ?latency,
+            status = response.status().as_u16(),
+            response_headers = ?self.redact(response.headers()),
+            "finished processing request"
+        );
+    }
+}
+
+impl Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.headers.iter().map(|(name, value)| {
+                let value: &dyn Debug = if self.redacted.contains(name) {
+                    &REDACTED
+                } else {
+                    value
+                };
+                (name, value)
+            }))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderMap, HeaderValue};
+
+    use super::{RedactedHeaders, RedactingTrace};
+
+    #[test]
+    fn redacts_configured_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer hunter2"));
+        headers.insert("x-spotify-token", HeaderValue::from_static("hunter3"));
+        headers.insert("accept", HeaderValue::from_static("text/html"));
+
+        let trace = RedactingTrace::new(RedactedHeaders::default());
+        let debug = format!("{:?}", trace.redact(&headers));
+        assert!(!debug.contains("hunter2"), "{debug}");
+        assert!(debug.contains("hunter3") && debug.contains("text/html"));
+
+        let names = vec!["X-Spotify-Token".to_owned()];
+        let trace = RedactingTrace::new(RedactedHeaders::try_from(names).unwrap());
+        let debug = format!("{:?}", trace.redact(&headers));
+        assert!(!debug.contains("hunter3"), "{debug}");
+        assert!(
+            debug.contains("hunter2"),
+            "Setting the list replaces the defaults"
+        );
+    }
+
+    #[test]
+    fn invalid_header_names_are_rejected() {
+        assert!(RedactedHeaders::try_from(vec!["not a header".to_owned()]).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+//! Zero-downtime upgrades by handing listeners over to a new copy of the binary.
+//!
+//! The running process passes its listening sockets to the new process the same way as socket
+//! activation (see [super::activation]) along with the write end of a pipe. The new process writes
+//! to the pipe once it serves the sockets, after which the old process drains and exits. Clients
+//! never see a closed socket because both processes share the same listening sockets.
+//!
+//! Under systemd, the old process tells systemd that the new process is the service's main process
+//! with `sd_notify(3)` so that its own exit doesn't stop the service.
+
+use std::{
+    env,
+    ffi::OsStr,
+    fs::File,
+    io::{self, Write},
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::{self, ffi::OsStrExt, net::UnixDatagram, process::CommandExt},
+    },
+    path::PathBuf,
+    process::{self, Command},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use nix::{
+    fcntl::{fcntl, FcntlArg, OFlag},
+    unistd::{dup2, pipe2},
+};
+use tokio::{io::AsyncReadExt, net::unix::pipe, time};
+use tracing::{debug, info, warn};
+
+use super::activation::{LISTEN_FDS_START, UPGRADE_PID_VAR};
+
+/// Descriptor of the pipe that the new process writes to once it's ready.
+const READY_FD_VAR: &str = "FANTASIA_READY_FD";
+
+/// Socket that systemd receives `sd_notify(3)` messages on.
+const NOTIFY_SOCKET_VAR: &str = "NOTIFY_SOCKET";
+
+/// Time to wait for the new process to report that it's ready.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Set once [notify_ready] has consumed the ready pipe.
+static NOTIFIED: AtomicBool = AtomicBool::new(false);
+
+/// Listening socket of a bound [super::Fantasia] instance that may be handed over to another
+/// process.
+#[derive(Debug)]
+pub struct ListenerFd {
+    fd: OwnedFd,
+    name: Option<String>,
+    handed_over: Arc<AtomicBool>,
+}
+
+impl ListenerFd {
+    /// `handed_over` is set once another process serves the socket.
+    pub(crate) fn new(fd: OwnedFd, name: Option<String>, handed_over: Arc<AtomicBool>) -> Self {
+        ListenerFd {
+            fd,
+            name,
+            handed_over,
+        }
+    }
+
+    /// Name passed to the new process in `LISTEN_FDNAMES`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+impl AsFd for ListenerFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+/// Start a new copy of this binary with the same arguments and hand `listeners` over to it.
+///
+/// Returns the PID of the new process once it reports that it's ready. The new process is killed
+/// if it isn't ready within [READY_TIMEOUT]. The caller should drain and exit afterwards.
+#[tracing::instrument(skip(listeners))]
+pub async fn handoff(listeners: &[ListenerFd]) -> io::Result<u32> {
+    let mut command = Command::new(executable()?);
+    command.args(env::args_os().skip(1));
+
+    let pid = spawn(command, listeners, READY_TIMEOUT).await?;
+    // Unix socket files now belong to the new process
+    for listener in listeners {
+        listener.handed_over.store(true, Ordering::SeqCst);
+    }
+
+    // Only the main process may change the main PID with the default `NotifyAccess=main`
+    if let Some(socket) = env::var_os(NOTIFY_SOCKET_VAR) {
+        match notify_systemd(&socket, &format!("MAINPID={pid}")) {
+            Ok(()) => info!("Told systemd that process {pid} is the main process"),
+            Err(e) => warn!("Couldn't tell systemd that process {pid} is the main process: {e}"),
+        }
+    }
+
+    Ok(pid)
+}
+
+/// Tell the process that handed its listeners over to this one that they're being served.
+///
+/// If this process wasn't started by [handoff], systemd is told that the service is ready instead
+/// when Fantasia runs as a `Type=notify` service.
+pub fn notify_ready() -> io::Result<()> {
+    let Some(parent) = env::var(UPGRADE_PID_VAR)
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .filter(|&pid| pid == unix::process::parent_id())
+    else {
+        return match env::var_os(NOTIFY_SOCKET_VAR) {
+            Some(socket) => notify_systemd(&socket, "READY=1"),
+            None => Ok(()),
+        };
+    };
+
+    let fd: RawFd = env::var(READY_FD_VAR)
+        .ok()
+        .and_then(|fd| fd.parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{READY_FD_VAR}` is not a valid file descriptor"),
+            )
+        })?;
+    if NOTIFIED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    // SAFETY: The parent passes ownership of the pipe's write end as `fd`, and `NOTIFIED` makes
+    // sure that it's only taken once.
+    let mut ready = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    ready.write_all(b"ready\n")?;
+    info!("Notified process {parent} that this process is ready");
+
+    Ok(())
+}
+
+/// Spawn `command` with `listeners` and wait up to `timeout` for it to report that it's ready.
+async fn spawn(
+    mut command: Command,
+    listeners: &[ListenerFd],
+    timeout: Duration,
+) -> io::Result<u32> {
+    let (ready_rx, ready_tx) = pipe2(OFlag::O_CLOEXEC)?;
+
+    let count = RawFd::try_from(listeners.len()).map_err(io::Error::other)?;
+    let names = listeners
+        .iter()
+        .map(|listener| listener.name().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join(":");
+    let mut fds: Vec<_> = listeners
+        .iter()
+        .map(|listener| listener.fd.as_raw_fd())
+        .chain([ready_tx.as_raw_fd()])
+        .collect();
+
+    command
+        .env_remove("LISTEN_PID")
+        .env("LISTEN_FDS", count.to_string())
+        .env("LISTEN_FDNAMES", names)
+        .env(UPGRADE_PID_VAR, process::id().to_string())
+        .env(READY_FD_VAR, (LISTEN_FDS_START + count).to_string());
+    // SAFETY: `remap_fds` only makes async-signal-safe system calls and doesn't allocate.
+    unsafe {
+        command.pre_exec(move || remap_fds(&mut fds));
+    }
+
+    let mut child = command.spawn()?;
+    // The pipe only reaches EOF if the child exits without reporting that it's ready
+    drop(ready_tx);
+    info!("Started process {} with {count} listeners", child.id());
+
+    let error = match time::timeout(timeout, wait_ready(ready_rx)).await {
+        Ok(Ok(())) => {
+            debug!("Process {} is ready", child.id());
+            return Ok(child.id());
+        }
+        Ok(Err(e)) => e,
+        Err(_) => io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("Process {} wasn't ready within {timeout:?}", child.id()),
+        ),
+    };
+
+    warn!("Stopping process {}", child.id());
+    // The child may have already exited
+    let _ = child.kill();
+    child.wait()?;
+
+    Err(error)
+}
+
+// Send `state` to systemd's notification `socket` like `sd_notify(3)`
+fn notify_systemd(socket: &OsStr, state: &str) -> io::Result<()> {
+    let sender = UnixDatagram::unbound()?;
+
+    // Abstract socket names start with `@`
+    match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+            let addr = SocketAddr::from_abstract_name(name)?;
+            sender.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Abstract `NOTIFY_SOCKET` addresses are only supported on Linux",
+            ))
+        }
+        None => {
+            sender.send_to(state.as_bytes(), socket)?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn wait_ready(ready: OwnedFd) -> io::Result<()> {
+    let mut ready = pipe::Receiver::from_owned_fd(ready)?;
+    let mut buf = [0; 16];
+
+    match ready.read(&mut buf).await? {
+        0 => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "New process exited before it was ready",
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Place `fds` at consecutive descriptors from `LISTEN_FDS_START` in the child.
+//
+// This runs between `fork` and `exec` so it may only make async-signal-safe calls.
+fn remap_fds(fds: &mut [RawFd]) -> io::Result<()> {
+    let end = LISTEN_FDS_START + fds.len() as RawFd;
+
+    // Move every descriptor past the target range first so that placing one doesn't replace
+    // another that hasn't been placed yet
+    for fd in fds.iter_mut() {
+        *fd = fcntl(*fd, FcntlArg::F_DUPFD_CLOEXEC(end))?;
+    }
+    // `dup2` clears close-on-exec so only the placed descriptors are inherited
+    for (target, &fd) in (LISTEN_FDS_START..).zip(fds.iter()) {
+        dup2(fd, target)?;
+    }
+
+    Ok(())
+}
+
+// `/proc/self/exe` points at a deleted file once a deployment replaces the binary
+fn executable() -> io::Result<PathBuf> {
+    let exe = env::current_exe()?;
+
+    match exe
+        .to_str()
+        .and_then(|path| path.strip_suffix(" (deleted)"))
+    {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => Ok(exe),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        net::TcpListener,
+        os::{fd::AsFd, unix::net::UnixDatagram},
+        process::{self, Command},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use super::{notify_systemd, spawn, ListenerFd};
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn listener() -> ListenerFd {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let fd = listener.as_fd().try_clone_to_owned().unwrap();
+
+        ListenerFd::new(fd, Some("https".into()), Arc::default())
+    }
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[tokio::test]
+    async fn ready_child_receives_listeners() {
+        let script = r#"
+            test "$LISTEN_FDS" = 2 &&
+            test "$LISTEN_FDNAMES" = "https:https" &&
+            test "$FANTASIA_UPGRADE_PID" = "$PPID" &&
+            test -S /dev/fd/3 &&
+            test -S /dev/fd/4 &&
+            echo ready >&"$FANTASIA_READY_FD"
+        "#;
+
+        spawn(sh(script), &[listener(), listener()], TIMEOUT)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn exited_child_fails() {
+        assert!(spawn(sh("exit 1"), &[listener()], TIMEOUT).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn slow_child_is_stopped() {
+        let error = spawn(sh("sleep 30"), &[listener()], Duration::from_millis(100))
+            .await
+            .unwrap_err();
+
+        assert_eq!(std::io::ErrorKind::TimedOut, error.kind());
+    }
+
+    #[test]
+    fn systemd_is_notified() {
+        let path = env::temp_dir().join(format!("fantasia-notify-{}.sock", process::id()));
+        let systemd = UnixDatagram::bind(&path).unwrap();
+
+        let sent = notify_systemd(path.as_os_str(), "MAINPID=42");
+        std::fs::remove_file(&path).unwrap();
+        sent.unwrap();
+
+        let mut buf = [0; 64];
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(b"MAINPID=42", &buf[..len]);
+    }
+}
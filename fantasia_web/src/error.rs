@@ -0,0 +1,155 @@
+//! Errors returned by handlers.
+//!
+//! [AppError] is rendered as an RFC 9457 [Problem] so that clients get the same document for every
+//! failure. Details that could help an attacker, such as SQL errors, are logged instead of sent.
+
+pub mod problem;
+
+pub use problem::Problem;
+
+use std::error::Error;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use tracing::{debug, error};
+
+/// Error type for Fantasia's handlers.
+#[derive(thiserror::Error, Debug)]
+pub enum AppError {
+    /// Postgres errors. Missing rows are `404 Not Found`, and exhausted pools are
+    /// `503 Service Unavailable`.
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    /// Invalid input from the client. The message is sent as the problem's `detail`.
+    #[error("Invalid request: {0}")]
+    Validation(String),
+    /// Missing or invalid credentials.
+    #[error("Authentication required")]
+    Unauthorized,
+    /// Valid credentials without permission for the resource.
+    #[error("Permission denied")]
+    Forbidden,
+    /// Errors from services that Fantasia calls, such as Spotify.
+    #[error("Upstream {service} failed: {source}")]
+    Upstream {
+        service: &'static str,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// Anything else that isn't the client's fault.
+    #[error("Internal error: {0}")]
+    Internal(Box<dyn Error + Send + Sync>),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Database(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+            AppError::Database(sqlx::Error::PoolTimedOut) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::Upstream { .. } => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// Problem document sent to the client.
+    pub fn problem(&self) -> Problem {
+        let problem = Problem::new(self.status());
+
+        match self {
+            AppError::Validation(message) => problem.detail(message.clone()),
+            _ => problem,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            error!(error = ?self, "{self}");
+        } else {
+            debug!("{self}");
+        }
+
+        self.problem().into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use axum::{
+        body,
+        http::{header, StatusCode},
+        response::IntoResponse,
+    };
+
+    use super::AppError;
+
+    async fn body(error: AppError) -> (StatusCode, String, String) {
+        let response = error.into_response();
+        let content_type = response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn internal_details_are_not_sent() {
+        let (status, content_type, body) = body(AppError::Database(sqlx::Error::Protocol(
+            "relation \"secret_table\" does not exist".into(),
+        )))
+        .await;
+
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status);
+        assert_eq!("application/problem+json", content_type);
+        assert!(!body.contains("secret_table"), "{body}");
+        assert!(body.contains(r#""status":500"#), "{body}");
+
+        let upstream = AppError::Upstream {
+            service: "Spotify",
+            source: Box::new(io::Error::other("token hunter2 expired")),
+        };
+        let (status, _, body) = self::body(upstream).await;
+        assert_eq!(StatusCode::BAD_GATEWAY, status);
+        assert!(!body.contains("hunter2"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn validation_messages_are_sent() {
+        let (status, _, body) =
+            body(AppError::Validation("`limit` must be at most 50".into())).await;
+
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, status);
+        assert!(body.contains("`limit` must be at most 50"), "{body}");
+    }
+
+    #[test]
+    fn database_errors_map_to_statuses() {
+        assert_eq!(
+            StatusCode::NOT_FOUND,
+            AppError::from(sqlx::Error::RowNotFound).status()
+        );
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            AppError::from(sqlx::Error::PoolTimedOut).status()
+        );
+        assert_eq!(StatusCode::UNAUTHORIZED, AppError::Unauthorized.status());
+        assert_eq!(StatusCode::FORBIDDEN, AppError::Forbidden.status());
+    }
+}
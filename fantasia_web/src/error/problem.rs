@@ -0,0 +1,274 @@
+//! [RFC 9457](https://www.rfc-editor.org/rfc/rfc9457) problem details.
+//!
+//! Problems are sent as `application/problem+json` unless the client prefers HTML, such as a
+//! browser, or plain text.
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::extract::request_id::REQUEST_ID_HEADER;
+
+/// Media type of problem documents.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Problem details document, e.g.
+/// `{"type":"about:blank","title":"Not Found","status":404,"request_id":"..."}`.
+///
+/// Problems don't define their own types, so `title` is the status's reason phrase. The router
+/// fills in `request_id` from the request's `x-request-id` and renders the problem in the format
+/// that the client accepts.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub title: &'static str,
+    pub status: u16,
+    /// Explanation specific to this occurrence that's safe to show the client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Representations of a [Problem] in order of preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Html,
+    Text,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode) -> Self {
+        Problem {
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or("Unknown"),
+            status: status.as_u16(),
+            detail: None,
+            request_id: None,
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Render the problem for a request that middleware outside the router rejects, since
+    /// [negotiate] doesn't see those responses.
+    pub(crate) fn render_for(mut self, headers: &HeaderMap) -> Response {
+        self.request_id = self.request_id.or_else(|| request_id(headers));
+        self.render(Format::negotiate(headers))
+    }
+
+    /// Render the problem as `format`.
+    pub fn render(&self, format: Format) -> Response {
+        let mut response = match format {
+            Format::Json => (
+                [(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON))],
+                Json(self),
+            )
+                .into_response(),
+            Format::Html => Html(self.html()).into_response(),
+            Format::Text => self.text().into_response(),
+        };
+
+        *response.status_mut() = self.status();
+        // Kept so that the router can render the problem again for the client
+        response.extensions_mut().insert(self.clone());
+        response
+    }
+
+    fn html(&self) -> String {
+        let title = format!("{} {}", self.status, escape(self.title));
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n"
+        );
+        if let Some(detail) = &self.detail {
+            html.push_str(&format!("<p>{}</p>\n", escape(detail)));
+        }
+        if let Some(request_id) = &self.request_id {
+            html.push_str(&format!("<p>Request ID: {}</p>\n", escape(request_id)));
+        }
+        html.push_str("</body>\n</html>\n");
+
+        html
+    }
+
+    fn text(&self) -> String {
+        let mut text = format!("{} {}\n", self.status, self.title);
+        if let Some(detail) = &self.detail {
+            text.push_str(&format!("{detail}\n"));
+        }
+        if let Some(request_id) = &self.request_id {
+            text.push_str(&format!("Request ID: {request_id}\n"));
+        }
+
+        text
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        self.render(Format::Json)
+    }
+}
+
+impl Format {
+    /// Format preferred by a request's `Accept` headers. Problems are JSON unless the client
+    /// prefers HTML or plain text.
+    pub fn negotiate(headers: &HeaderMap) -> Format {
+        let ranges: Vec<(&str, f32)> = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(media_range)
+            .collect();
+        if ranges.is_empty() {
+            return Format::Json;
+        }
+
+        let mut best = (Format::Json, 0.0);
+        for format in [Format::Json, Format::Html, Format::Text] {
+            let quality = format.quality(&ranges);
+            if quality > best.1 {
+                best = (format, quality);
+            }
+        }
+
+        best.0
+    }
+
+    // Quality of the most specific range that matches the format
+    fn quality(self, ranges: &[(&str, f32)]) -> f32 {
+        let (types, family): (&[&str], &str) = match self {
+            Format::Json => (&[PROBLEM_JSON, "application/json"], "application/*"),
+            Format::Html => (&["text/html"], "text/*"),
+            Format::Text => (&["text/plain"], "text/*"),
+        };
+
+        [types, &[family], &["*/*"]]
+            .into_iter()
+            .find_map(|candidates| {
+                ranges
+                    .iter()
+                    .filter(|(range, _)| candidates.iter().any(|ty| range.eq_ignore_ascii_case(ty)))
+                    .map(|&(_, quality)| quality)
+                    .reduce(f32::max)
+            })
+            .unwrap_or(0.0)
+    }
+}
+
+/// Render [Problem] responses in the format the client accepts and add the request's
+/// `x-request-id`.
+pub(crate) async fn negotiate(request: Request, next: Next) -> Response {
+    let format = Format::negotiate(request.headers());
+    let request_id = request_id(request.headers());
+    let response = next.run(request).await;
+
+    let Some(problem) = response.extensions().get::<Problem>() else {
+        return response;
+    };
+    let problem = Problem {
+        request_id: problem.request_id.clone().or(request_id),
+        ..problem.clone()
+    };
+
+    let (mut parts, _) = response.into_parts();
+    let (rendered, body) = problem.render(format).into_parts();
+
+    // Keep headers added by the handler and other middleware, such as `Allow`
+    parts.headers.extend(rendered.headers);
+    parts.extensions.extend(rendered.extensions);
+    Response::from_parts(parts, body)
+}
+
+fn request_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .map(ToOwned::to_owned)
+}
+
+// Media range and quality of one element of `Accept`, e.g. `text/html;q=0.9`
+fn media_range(element: &str) -> Option<(&str, f32)> {
+    let mut params = element.split(';');
+    let range = params.next()?.trim();
+    if range.is_empty() {
+        return None;
+    }
+
+    let quality = params
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+        .and_then(|(_, q)| q.trim().parse().ok())
+        .unwrap_or(1.0);
+
+    Some((range, quality))
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+
+    use super::{Format, Problem};
+
+    fn accept(value: &'static str) -> Format {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+        Format::negotiate(&headers)
+    }
+
+    #[test]
+    fn negotiates_formats() {
+        assert_eq!(Format::Json, Format::negotiate(&HeaderMap::new()));
+        assert_eq!(Format::Json, accept("*/*"));
+        assert_eq!(Format::Json, accept("application/json"));
+        assert_eq!(Format::Text, accept("text/plain"));
+        assert_eq!(Format::Json, accept("image/png"));
+        // Firefox
+        assert_eq!(
+            Format::Html,
+            accept("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+        );
+        assert_eq!(Format::Text, accept("text/html;q=0.5, text/plain"));
+        assert_eq!(Format::Json, accept("application/*, text/*;q=0.9"));
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        let html = Problem::new(StatusCode::UNPROCESSABLE_ENTITY)
+            .detail("<script>alert(1)</script>")
+            .html();
+
+        assert!(html.contains("&lt;script&gt;"), "{html}");
+        assert!(html.contains("<h1>422 Unprocessable Entity</h1>"), "{html}");
+    }
+}
@@ -0,0 +1,7 @@
+//! Extractors for request data that Fantasia resolves before routing.
+
+pub mod client_ip;
+pub mod request_id;
+
+pub use client_ip::{ClientIp, TrustedProxies};
+pub use request_id::RequestId;
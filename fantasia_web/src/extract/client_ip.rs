@@ -0,0 +1,344 @@
+//! Client addresses of requests that pass through reverse proxies.
+//!
+//! Requests forwarded by a proxy or load balancer arrive from the proxy's address. Proxies report
+//! the address they received a request from in `Forwarded`, `X-Forwarded-For`, or `X-Real-IP`, but
+//! clients may send those headers as well. Hops are only believed if they were added by one of the
+//! [TrustedProxies]: the reported addresses are walked from the nearest hop outwards, and the first
+//! address that isn't a trusted proxy is the client.
+
+use std::{
+    fmt::{self, Display},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::FORWARDED, request::Parts, HeaderMap, HeaderValue},
+};
+use ipnet::IpNet;
+use serde::Deserialize;
+
+use crate::{app::PeerAddr, error::AppError};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_REAL_IP: &str = "x-real-ip";
+
+/// Address of the client that sent a request.
+///
+/// The router resolves this once per request with [TrustedProxies::resolve] so that handlers and
+/// trace spans agree on the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientIp {
+    /// Address of a TCP peer or of the client reported by a trusted proxy.
+    Ip(IpAddr),
+    /// Process on this host connected over a Unix socket. Unix socket peers don't have an address,
+    /// so they can't be told apart unless a trusted proxy reports one.
+    Local,
+}
+
+/// Networks of reverse proxies whose forwarding headers are trusted, such as `10.0.0.0/8`.
+///
+/// Bare addresses are single hosts. Unix socket peers are trusted if `127.0.0.1` is, so trust that
+/// address for a reverse proxy on the same host. Nothing is trusted by default, in which case the
+/// client is always the connection's peer.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(try_from = "Vec<String>")]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<IpNet>) -> Self {
+        TrustedProxies(networks)
+    }
+
+    /// Whether `ip` belongs to a trusted proxy.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|network| network.contains(ip))
+    }
+
+    /// Resolve the client's address from the connection's `peer` and the request's `headers`.
+    ///
+    /// `Forwarded` takes precedence over `X-Forwarded-For`, which takes precedence over
+    /// `X-Real-IP`. Hops that don't have an IP address, such as `unknown` or obfuscated
+    /// identifiers, end the walk at the last trusted hop.
+    pub fn resolve(&self, peer: &PeerAddr, headers: &HeaderMap) -> ClientIp {
+        let (client, peer) = match peer {
+            PeerAddr::Tcp(addr) => {
+                let ip = addr.ip().to_canonical();
+                (ClientIp::Ip(ip), ip)
+            }
+            PeerAddr::Unix(_) => (ClientIp::Local, IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        };
+        if !self.contains(&peer) {
+            return client;
+        }
+
+        let Some(hops) = forwarded(headers)
+            .or_else(|| x_forwarded_for(headers))
+            .or_else(|| x_real_ip(headers))
+        else {
+            return client;
+        };
+
+        let mut client = client;
+        for hop in hops.into_iter().rev() {
+            let Some(ip) = hop else {
+                break;
+            };
+            let ip = ip.to_canonical();
+            client = ClientIp::Ip(ip);
+            if !self.contains(&ip) {
+                break;
+            }
+        }
+
+        client
+    }
+}
+
+impl TryFrom<Vec<String>> for TrustedProxies {
+    type Error = String;
+
+    fn try_from(networks: Vec<String>) -> Result<Self, Self::Error> {
+        networks
+            .iter()
+            .map(|network| {
+                network
+                    .parse::<IpNet>()
+                    .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("Invalid trusted proxy network `{network}`"))
+            })
+            .collect::<Result<_, _>>()
+            .map(TrustedProxies)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ClientIp>()
+            .copied()
+            .ok_or_else(|| AppError::Internal("Client address was not resolved".into()))
+    }
+}
+
+impl Display for ClientIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientIp::Ip(ip) => write!(f, "{ip}"),
+            ClientIp::Local => f.write_str("local"),
+        }
+    }
+}
+
+/// `for` parameters of RFC 7239 `Forwarded` headers, e.g. `for=192.0.2.60;proto=https`.
+fn forwarded(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    hops(headers, FORWARDED.as_str(), |element| {
+        element
+            .split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+            .and_then(|(_, value)| node(value))
+    })
+}
+
+/// Comma separated addresses of `X-Forwarded-For`, e.g. `203.0.113.195, 70.41.3.18`.
+fn x_forwarded_for(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    hops(headers, X_FORWARDED_FOR, node)
+}
+
+/// Single address of `X-Real-IP`.
+fn x_real_ip(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let value = headers.get(X_REAL_IP)?;
+    Some(vec![value.to_str().ok().and_then(node)])
+}
+
+// Hops from every `name` header in order. Each header may list several comma separated hops.
+fn hops(
+    headers: &HeaderMap,
+    name: &str,
+    parse: impl Fn(&str) -> Option<IpAddr>,
+) -> Option<Vec<Option<IpAddr>>> {
+    let values: Vec<&HeaderValue> = headers.get_all(name).iter().collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    Some(
+        values
+            .into_iter()
+            .flat_map(|value| match value.to_str() {
+                Ok(value) => value.split(',').map(&parse).collect(),
+                Err(_) => vec![None],
+            })
+            .collect(),
+    )
+}
+
+// Address of a single hop, optionally quoted and with a port (e.g. `"[2001:db8::1]:4711"`)
+fn node(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+
+    if let Some(rest) = value.strip_prefix('[') {
+        let (ip, _) = rest.split_once(']')?;
+        return ip.parse().ok();
+    }
+
+    value
+        .parse()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use axum::http::{HeaderMap, HeaderValue};
+
+    use super::{ClientIp, TrustedProxies};
+    use crate::app::PeerAddr;
+
+    fn trusted() -> TrustedProxies {
+        TrustedProxies::try_from(vec!["10.0.0.0/8".to_owned(), "127.0.0.1".to_owned()]).unwrap()
+    }
+
+    fn peer(addr: &str) -> PeerAddr {
+        PeerAddr::Tcp(SocketAddr::new(addr.parse().unwrap(), 443))
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    fn ip(addr: &str) -> ClientIp {
+        ClientIp::Ip(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn untrusted_peer_is_the_client() {
+        let headers = headers(&[("x-forwarded-for", "192.0.2.1")]);
+
+        assert_eq!(
+            ip("203.0.113.7"),
+            trusted().resolve(&peer("203.0.113.7"), &headers)
+        );
+        assert_eq!(
+            ip("10.0.0.1"),
+            TrustedProxies::default().resolve(&peer("10.0.0.1"), &headers)
+        );
+    }
+
+    #[test]
+    fn walks_trusted_hops_from_the_right() {
+        // The leftmost address was supplied by the client and isn't trusted
+        let headers = headers(&[("x-forwarded-for", "198.51.100.9, 192.0.2.1, 10.1.1.1")]);
+
+        assert_eq!(
+            ip("192.0.2.1"),
+            trusted().resolve(&peer("10.0.0.1"), &headers)
+        );
+    }
+
+    #[test]
+    fn every_trusted_hop_yields_the_leftmost() {
+        let headers = headers(&[("x-forwarded-for", "10.2.2.2, 10.1.1.1")]);
+
+        assert_eq!(
+            ip("10.2.2.2"),
+            trusted().resolve(&peer("10.0.0.1"), &headers)
+        );
+    }
+
+    #[test]
+    fn forwarded_takes_precedence() {
+        let headers = headers(&[
+            ("x-forwarded-for", "198.51.100.9"),
+            ("x-real-ip", "198.51.100.10"),
+            (
+                "forwarded",
+                r#"for=192.0.2.60;proto=https, For="[2001:db8:cafe::17]:4711""#,
+            ),
+        ]);
+
+        assert_eq!(
+            ip("2001:db8:cafe::17"),
+            trusted().resolve(&peer("10.0.0.1"), &headers)
+        );
+    }
+
+    #[test]
+    fn repeated_headers_are_joined() {
+        let headers = headers(&[
+            ("x-forwarded-for", "192.0.2.1"),
+            ("x-forwarded-for", "10.1.1.1"),
+        ]);
+
+        assert_eq!(
+            ip("192.0.2.1"),
+            trusted().resolve(&peer("10.0.0.1"), &headers)
+        );
+    }
+
+    #[test]
+    fn unknown_hops_stop_at_the_last_trusted_hop() {
+        let headers = headers(&[("forwarded", "for=192.0.2.1, for=_hidden, for=10.1.1.1")]);
+
+        assert_eq!(
+            ip("10.1.1.1"),
+            trusted().resolve(&peer("10.0.0.1"), &headers)
+        );
+    }
+
+    #[test]
+    fn real_ip_from_trusted_peer() {
+        let headers = headers(&[("x-real-ip", "192.0.2.1")]);
+
+        assert_eq!(
+            ip("192.0.2.1"),
+            trusted().resolve(&peer("127.0.0.1"), &headers)
+        );
+        assert_eq!(
+            ip("192.0.2.1"),
+            trusted().resolve(&PeerAddr::Unix(None), &headers)
+        );
+    }
+
+    #[test]
+    fn unix_peers_are_local_without_a_trusted_proxy() {
+        let headers = headers(&[("x-forwarded-for", "192.0.2.1")]);
+        let unix = PeerAddr::Unix(None);
+
+        assert_eq!(ClientIp::Local, trusted().resolve(&unix, &HeaderMap::new()));
+        assert_eq!(
+            ClientIp::Local,
+            TrustedProxies::default().resolve(&unix, &headers)
+        );
+    }
+
+    #[test]
+    fn mapped_addresses_are_canonical() {
+        let headers = headers(&[("x-forwarded-for", "::ffff:192.0.2.1")]);
+
+        assert_eq!(
+            ip("192.0.2.1"),
+            trusted().resolve(&peer("::ffff:10.0.0.1"), &headers)
+        );
+    }
+
+    #[test]
+    fn invalid_networks_are_rejected() {
+        assert!(TrustedProxies::try_from(vec!["10.0.0.0/33".to_owned()]).is_err());
+        assert!(TrustedProxies::try_from(vec!["localhost".to_owned()]).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Request IDs for correlating logs with requests.
+//!
+//! Every request gets an `x-request-id`, which is also sent back with the response. IDs sent by
+//! clients or proxies are kept if they're valid so that logs can be correlated across services.
+//! Otherwise, the router generates a UUID.
+
+use std::fmt::{self, Display};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, HeaderName, HeaderValue},
+};
+use tracing::debug;
+
+use crate::error::AppError;
+
+/// Header that carries the request ID.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest request ID accepted from clients.
+pub const MAX_LEN: usize = 128;
+
+/// ID of the current request from `x-request-id`.
+///
+/// The router's root span records the ID as the `request_id` field, so spans and events within the
+/// request can be correlated with it. Nothing forwards the ID to other services by itself; add
+/// [RequestId::header] to outgoing requests for that.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// Header for forwarding the ID to other services.
+    pub fn header(&self) -> (HeaderName, HeaderValue) {
+        let value = HeaderValue::try_from(&self.0).expect("Request IDs are valid header values");
+        (REQUEST_ID_HEADER, value)
+    }
+}
+
+/// Whether a client's request ID may be used.
+///
+/// IDs have between 1 and [MAX_LEN] ASCII letters, digits, or `-`, `_`, `.`, `:`, `+`, `=`, `/`.
+/// That covers UUIDs, ULIDs, and the IDs of common proxies while keeping logs parseable.
+pub fn is_valid(id: &HeaderValue) -> bool {
+    let id = id.as_bytes();
+
+    (1..=MAX_LEN).contains(&id.len())
+        && id
+            .iter()
+            .all(|&byte| byte.is_ascii_alphanumeric() || b"-_.:+=/".contains(&byte))
+}
+
+/// Remove invalid `x-request-id` headers so that the router replaces them.
+pub(crate) fn drop_invalid(mut request: Request) -> Request {
+    let headers = request.headers_mut();
+    let ids = headers.get_all(&REQUEST_ID_HEADER).iter().count();
+
+    let valid = match headers.get(&REQUEST_ID_HEADER) {
+        Some(id) => ids == 1 && is_valid(id),
+        None => return request,
+    };
+    if !valid {
+        debug!("Replacing invalid request ID");
+        headers.remove(&REQUEST_ID_HEADER);
+    }
+
+    request
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestId
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .headers
+            .get(&REQUEST_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .map(|id| RequestId(id.to_owned()))
+            .ok_or_else(|| AppError::Internal("Request ID was not set".into()))
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::FromRequestParts,
+        http::{header, HeaderValue, Request, StatusCode},
+        response::IntoResponse,
+    };
+
+    use super::{is_valid, RequestId, MAX_LEN};
+    use crate::error::problem::PROBLEM_JSON;
+
+    #[test]
+    fn accepts_common_ids() {
+        for id in [
+            "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+            "Root=1-5759e988-bd862e3fe1be46a994272793",
+            "req_abc.123",
+        ] {
+            assert!(is_valid(&HeaderValue::from_static(id)), "{id}");
+        }
+    }
+
+    #[test]
+    fn rejects_unusual_ids() {
+        let long = "a".repeat(MAX_LEN + 1);
+
+        for id in ["", "has spaces", "quote\"d", "new\tline", long.as_str()] {
+            assert!(!is_valid(&HeaderValue::try_from(id).unwrap()), "{id}");
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_id_is_a_problem() {
+        let (mut parts, ()) = Request::new(()).into_parts();
+        let rejection = RequestId::from_request_parts(&mut parts, &())
+            .await
+            .unwrap_err()
+            .into_response();
+
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, rejection.status());
+        assert_eq!(PROBLEM_JSON, rejection.headers()[header::CONTENT_TYPE]);
+    }
+}
@@ -1,21 +1,21 @@
 pub mod app;
+pub mod error;
+pub mod extract;
+pub mod metrics;
 pub mod routes;
 pub mod state;
+pub mod telemetry;
 
 // Reexports
 pub use axum::http::StatusCode;
 pub use sqlx::{pool::PoolOptions, postgres::PgPoolOptions, PgPool};
 
-use std::net::SocketAddr;
+use std::io;
 
-use axum::{
-    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
-    middleware::AddExtension,
-    serve, Router,
-};
+use futures::future::BoxFuture;
 
-/// Axum's [axum::serve::Serve] with bounds for a Fantasia instance
-pub type Serve = serve::Serve<
-    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
-    AddExtension<Router, ConnectInfo<SocketAddr>>,
->;
+/// Future that drives a Fantasia instance until it stops.
+///
+/// The future resolves after a requested shutdown has drained in-flight requests or the grace
+/// period has elapsed.
+pub type Serve = BoxFuture<'static, io::Result<()>>;
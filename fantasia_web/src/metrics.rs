@@ -0,0 +1,142 @@
+//! Operational metrics in the OpenMetrics text format.
+//!
+//! Metrics are exported by [crate::routes::metrics].
+
+pub mod pool;
+#[cfg(target_os = "linux")]
+pub mod process;
+
+use std::{fmt, time::Duration};
+
+use prometheus_client::{
+    encoding::{text, EncodeLabelSet},
+    metrics::{counter::Counter, family::Family, gauge::Gauge, histogram::Histogram},
+    registry::{Registry, Unit},
+};
+
+/// Content type of the OpenMetrics text format.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Upper bounds of latency buckets in seconds, which are the Prometheus clients' defaults.
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Latency histograms by label.
+pub type LatencyHistograms<L> = Family<L, Histogram, fn() -> Histogram>;
+
+/// Metrics shared by every router of a [crate::app::FantasiaBuilder].
+#[derive(Debug)]
+pub struct Metrics {
+    /// Requests rejected by the rate limiter by route group.
+    pub rate_limited: Family<GroupLabels, Counter>,
+    /// Handlers that panicked.
+    pub panics: Counter,
+    /// Finished requests by route, method, and status.
+    pub requests: Family<RequestLabels, Counter>,
+    /// Latency of finished requests by route, method, and status.
+    pub request_duration: LatencyHistograms<RequestLabels>,
+    /// Requests that haven't been responded to yet.
+    pub in_flight: Gauge,
+    /// Time spent waiting for Postgres connections acquired by [crate::state::Database::acquire].
+    pub pool_acquire_wait: Histogram,
+    registry: Registry,
+}
+
+/// Labels of a request. `route` is the matched route, such as `/users/:id`, so that paths with
+/// IDs share a series.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+pub struct RequestLabels {
+    pub route: String,
+    pub method: String,
+    pub status: u16,
+}
+
+/// Label of a rate limiter route group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+pub struct GroupLabels {
+    pub group: String,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let metrics = Metrics {
+            rate_limited: Family::default(),
+            panics: Counter::default(),
+            requests: Family::default(),
+            request_duration: Family::new_with_constructor(latency_histogram),
+            in_flight: Gauge::default(),
+            pool_acquire_wait: latency_histogram(),
+            registry: Registry::default(),
+        };
+
+        let mut registry = Registry::default();
+        registry.register(
+            "fantasia_http_requests",
+            "HTTP requests by matched route, method, and status.",
+            metrics.requests.clone(),
+        );
+        registry.register_with_unit(
+            "fantasia_http_request_duration",
+            "Time until response headers were sent by matched route, method, and status.",
+            Unit::Seconds,
+            metrics.request_duration.clone(),
+        );
+        registry.register(
+            "fantasia_http_requests_in_flight",
+            "HTTP requests being handled.",
+            metrics.in_flight.clone(),
+        );
+        registry.register(
+            "fantasia_rate_limited",
+            "Requests rejected by the rate limiter by route group.",
+            metrics.rate_limited.clone(),
+        );
+        registry.register(
+            "fantasia_panics",
+            "Handlers that panicked.",
+            metrics.panics.clone(),
+        );
+        registry.register_with_unit(
+            "fantasia_db_pool_acquire_wait",
+            "Time spent waiting for a Postgres connection.",
+            Unit::Seconds,
+            metrics.pool_acquire_wait.clone(),
+        );
+
+        Metrics {
+            registry,
+            ..metrics
+        }
+    }
+}
+
+impl Metrics {
+    /// Record a finished request.
+    pub fn observe_request(&self, labels: RequestLabels, latency: Duration) {
+        self.request_duration
+            .get_or_create(&labels)
+            .observe(latency.as_secs_f64());
+        self.requests.get_or_create(&labels).inc();
+    }
+
+    /// Count a request rejected by the rate limiter in `group`.
+    pub fn observe_rate_limited(&self, group: &str) {
+        self.rate_limited
+            .get_or_create(&GroupLabels {
+                group: group.to_owned(),
+            })
+            .inc();
+    }
+
+    /// Add Fantasia's own metrics to `out`.
+    ///
+    /// The exposition isn't terminated so that the scrape may add more metrics.
+    pub fn encode(&self, out: &mut String) -> fmt::Result {
+        text::encode_registry(out, &self.registry)
+    }
+}
+
+fn latency_histogram() -> Histogram {
+    Histogram::new(LATENCY_BUCKETS)
+}
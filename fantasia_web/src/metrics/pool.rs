@@ -0,0 +1,50 @@
+//! Size of the Postgres pool at the time of a scrape.
+
+use std::fmt;
+
+use prometheus_client::{
+    collector::Collector,
+    encoding::{DescriptorEncoder, EncodeMetric},
+    metrics::gauge::ConstGauge,
+};
+use sqlx::PgPool;
+
+/// Reports the pool's connection counts without acquiring a connection.
+#[derive(Debug)]
+pub struct PoolMetrics {
+    pool: PgPool,
+}
+
+impl PoolMetrics {
+    pub fn new(pool: PgPool) -> PoolMetrics {
+        PoolMetrics { pool }
+    }
+}
+
+impl Collector for PoolMetrics {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> fmt::Result {
+        let gauges = [
+            (
+                "fantasia_db_pool_connections",
+                "Open Postgres connections, including idle ones.",
+                self.pool.size() as u64,
+            ),
+            (
+                "fantasia_db_pool_idle_connections",
+                "Idle Postgres connections.",
+                self.pool.num_idle() as u64,
+            ),
+            (
+                "fantasia_db_pool_max_connections",
+                "Most Postgres connections the pool opens.",
+                self.pool.options().get_max_connections() as u64,
+            ),
+        ];
+
+        for (name, help, value) in gauges {
+            let gauge = ConstGauge::new(value);
+            gauge.encode(encoder.encode_descriptor(name, help, None, gauge.metric_type())?)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+//! Standard Prometheus process metrics read from `/proc`.
+
+use std::{fmt, fs, io};
+
+use nix::unistd::{sysconf, SysconfVar};
+use prometheus_client::{
+    collector::Collector,
+    encoding::{DescriptorEncoder, EncodeMetric},
+    metrics::{counter::ConstCounter, gauge::ConstGauge},
+};
+
+/// Resource usage of the current process.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessMetrics {
+    /// User and system CPU time.
+    pub cpu_seconds: f64,
+    pub resident_memory_bytes: u64,
+    pub virtual_memory_bytes: u64,
+    pub open_fds: u64,
+    /// Soft limit of open file descriptors.
+    pub max_fds: Option<u64>,
+    /// Seconds since the Unix epoch.
+    pub start_time_seconds: f64,
+    pub threads: u64,
+}
+
+impl ProcessMetrics {
+    /// Read the current process' metrics.
+    pub fn collect() -> io::Result<ProcessMetrics> {
+        let ticks = sysconf_var(SysconfVar::CLK_TCK)? as f64;
+        let page_size = sysconf_var(SysconfVar::PAGE_SIZE)?;
+
+        let stat = fs::read_to_string("/proc/self/stat")?;
+        // The executable's name is in parentheses and may contain spaces
+        let fields: Vec<&str> = stat
+            .rsplit_once(')')
+            .map(|(_, fields)| fields.split_whitespace().collect())
+            .unwrap_or_default();
+        // `fields` starts at the third field of proc_pid_stat(5)
+        let field = |n: usize| -> io::Result<u64> {
+            fields
+                .get(n - 3)
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| invalid(format!("/proc/self/stat is missing field {n}")))
+        };
+
+        let boot_time = fs::read_to_string("/proc/stat")?
+            .lines()
+            .find_map(|line| line.strip_prefix("btime "))
+            .and_then(|btime| btime.trim().parse::<u64>().ok())
+            .ok_or_else(|| invalid("/proc/stat is missing btime".into()))?;
+
+        Ok(ProcessMetrics {
+            cpu_seconds: (field(14)? + field(15)?) as f64 / ticks,
+            threads: field(20)?,
+            start_time_seconds: boot_time as f64 + field(22)? as f64 / ticks,
+            virtual_memory_bytes: field(23)?,
+            resident_memory_bytes: field(24)? * page_size,
+            open_fds: fs::read_dir("/proc/self/fd")?.count() as u64,
+            max_fds: max_fds(&fs::read_to_string("/proc/self/limits")?),
+        })
+    }
+}
+
+impl Collector for ProcessMetrics {
+    /// Encode the metrics with the names used by Prometheus' client libraries.
+    fn encode(&self, mut encoder: DescriptorEncoder) -> fmt::Result {
+        let cpu = ConstCounter::new(self.cpu_seconds);
+        cpu.encode(encoder.encode_descriptor(
+            "process_cpu_seconds",
+            "Total user and system CPU time spent in seconds.",
+            None,
+            cpu.metric_type(),
+        )?)?;
+
+        let gauges = [
+            (
+                "process_resident_memory_bytes",
+                "Resident memory size in bytes.",
+                Some(self.resident_memory_bytes),
+            ),
+            (
+                "process_virtual_memory_bytes",
+                "Virtual memory size in bytes.",
+                Some(self.virtual_memory_bytes),
+            ),
+            (
+                "process_open_fds",
+                "Number of open file descriptors.",
+                Some(self.open_fds),
+            ),
+            (
+                "process_max_fds",
+                "Maximum number of open file descriptors.",
+                self.max_fds,
+            ),
+            (
+                "process_threads",
+                "Number of OS threads.",
+                Some(self.threads),
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let Some(value) = value else { continue };
+            let gauge = ConstGauge::new(value);
+            gauge.encode(encoder.encode_descriptor(name, help, None, gauge.metric_type())?)?;
+        }
+
+        let start_time = ConstGauge::new(self.start_time_seconds);
+        start_time.encode(encoder.encode_descriptor(
+            "process_start_time_seconds",
+            "Start time of the process since unix epoch in seconds.",
+            None,
+            start_time.metric_type(),
+        )?)
+    }
+}
+
+fn sysconf_var(var: SysconfVar) -> io::Result<u64> {
+    match sysconf(var) {
+        Ok(Some(value)) if value > 0 => Ok(value as u64),
+        Ok(_) => Err(invalid(format!("{var:?} isn't available"))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Soft limit from "Max open files  1024  524288  files"; `None` if unlimited
+fn max_fds(limits: &str) -> Option<u64> {
+    limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))
+        .and_then(|limits| limits.split_whitespace().next())
+        .and_then(|soft| soft.parse().ok())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{max_fds, ProcessMetrics};
+
+    #[test]
+    fn reads_current_process() {
+        let metrics = ProcessMetrics::collect().expect("/proc should be readable on Linux");
+
+        assert!(metrics.threads >= 1);
+        assert!(metrics.open_fds >= 1);
+        assert!(metrics.resident_memory_bytes > 0);
+        assert!(metrics.start_time_seconds > 0.0);
+    }
+
+    #[test]
+    fn parses_max_fds() {
+        let limits = "Limit                     Soft Limit           Hard Limit           Units     \n\
+                      Max open files            1024                 524288               files     \n";
+
+        assert_eq!(Some(1024), max_fds(limits));
+        assert_eq!(
+            None,
+            max_fds("Max open files            unlimited            unlimited            files")
+        );
+    }
+}
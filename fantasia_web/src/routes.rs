@@ -3,7 +3,13 @@
 pub mod fallback_404;
 pub mod health;
 pub mod index;
+pub mod method_not_allowed;
+pub mod metrics;
+pub mod pool;
 
 pub use fallback_404::fallback_404;
-pub use health::health_check;
+pub use health::{health_check, live, ready};
 pub use index::index;
+pub use method_not_allowed::method_not_allowed;
+pub use metrics::prometheus_metrics;
+pub use pool::pool_stats;
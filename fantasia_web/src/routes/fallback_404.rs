@@ -1,5 +1,7 @@
 use axum::http::StatusCode;
 
-pub async fn fallback_404() -> StatusCode {
-    StatusCode::NOT_FOUND
+use crate::error::Problem;
+
+pub async fn fallback_404() -> Problem {
+    Problem::new(StatusCode::NOT_FOUND)
 }
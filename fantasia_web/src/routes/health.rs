@@ -1,10 +1,221 @@
-use std::net::SocketAddr;
+use std::{collections::HashSet, future::Future, time::Duration};
 
-use axum::extract::ConnectInfo;
-use tracing::trace;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use sqlx::{migrate::Migrator, PgPool};
+use tokio::time;
+use tracing::{trace, warn};
+
+use crate::{extract::ClientIp, state::Database};
+
+/// Migrations that Fantasia's schema should have.
+pub static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
+
+/// Time each readiness check may take before it fails.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Health and sanity check endpoint.
 #[tracing::instrument(level = "debug")]
-pub async fn health_check(ConnectInfo(addr): ConnectInfo<SocketAddr>) {
-    trace!("Connected: {addr}")
+pub async fn health_check(client: ClientIp) {
+    trace!("Connected: {client}")
+}
+
+/// Outcome of a check.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Fail,
+}
+
+/// Why a check's query didn't return.
+#[derive(thiserror::Error, Debug)]
+enum CheckError {
+    #[error("{0}")]
+    QueryFailed(sqlx::Error),
+    #[error("Timed out after {CHECK_TIMEOUT:?}")]
+    TimedOut,
+}
+
+/// Liveness of the process.
+#[derive(Serialize, Debug)]
+pub struct Liveness {
+    pub status: Status,
+}
+
+/// Readiness to serve requests broken down by dependency.
+#[derive(Serialize, Debug)]
+pub struct Readiness {
+    /// [Status::Fail] if any check failed.
+    pub status: Status,
+    pub postgres: PostgresCheck,
+    pub pool: PoolCheck,
+    pub migrations: MigrationCheck,
+}
+
+/// Round trip to Postgres.
+#[derive(Serialize, Debug)]
+pub struct PostgresCheck {
+    pub status: Status,
+    pub latency_ms: u128,
+    /// Why the check failed. Details are logged rather than sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+}
+
+/// Usage of the Postgres pool. A saturated pool is busy rather than broken, so it doesn't fail.
+#[derive(Serialize, Debug)]
+pub struct PoolCheck {
+    pub status: Status,
+    pub size: u32,
+    pub idle: usize,
+    pub in_use: usize,
+    pub max_connections: u32,
+    /// Every connection is open and in use, so requests wait for a connection.
+    pub saturated: bool,
+}
+
+/// Migrations embedded in Fantasia that haven't been applied to the database.
+#[derive(Serialize, Debug)]
+pub struct MigrationCheck {
+    pub status: Status,
+    /// Versions of pending migrations.
+    pub pending: Vec<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+}
+
+/// Liveness probe, which succeeds as long as Fantasia can respond.
+#[tracing::instrument(level = "debug")]
+pub async fn live() -> Json<Liveness> {
+    Json(Liveness { status: Status::Ok })
+}
+
+/// Readiness probe, which fails with `503 Service Unavailable` if a dependency is unhealthy.
+#[tracing::instrument(level = "debug", skip(db))]
+pub async fn ready(State(db): State<Database>) -> (StatusCode, Json<Readiness>) {
+    let (postgres, migrations) = futures::join!(ping(&db), migrations(&db.pool));
+    let pool = pool(&db.pool);
+
+    let status = if [postgres.status, pool.status, migrations.status].contains(&Status::Fail) {
+        Status::Fail
+    } else {
+        Status::Ok
+    };
+    let code = match status {
+        Status::Ok => StatusCode::OK,
+        Status::Fail => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (
+        code,
+        Json(Readiness {
+            status,
+            postgres,
+            pool,
+            migrations,
+        }),
+    )
+}
+
+async fn ping(db: &Database) -> PostgresCheck {
+    let start = time::Instant::now();
+    let query = async {
+        let mut conn = db.acquire().await?;
+        sqlx::query("SELECT 1").execute(&mut *conn).await
+    };
+    let result = check_query(query).await;
+    let latency_ms = start.elapsed().as_millis();
+
+    let error = result.err().map(|e| {
+        warn!("Readiness check couldn't query Postgres: {e}");
+        e.reason()
+    });
+
+    PostgresCheck {
+        status: if error.is_some() {
+            Status::Fail
+        } else {
+            Status::Ok
+        },
+        latency_ms,
+        error,
+    }
+}
+
+// Run a check's query with the check's time limit
+async fn check_query<T>(
+    query: impl Future<Output = Result<T, sqlx::Error>>,
+) -> Result<T, CheckError> {
+    time::timeout(CHECK_TIMEOUT, query)
+        .await
+        .map_err(|_| CheckError::TimedOut)?
+        .map_err(CheckError::QueryFailed)
+}
+
+impl CheckError {
+    // Reason sent to the client for a failed check
+    fn reason(&self) -> &'static str {
+        match self {
+            CheckError::QueryFailed(_) => "query failed",
+            CheckError::TimedOut => "timed out",
+        }
+    }
+}
+
+fn pool(pool: &PgPool) -> PoolCheck {
+    let size = pool.size();
+    let idle = pool.num_idle();
+    let max_connections = pool.options().get_max_connections();
+
+    PoolCheck {
+        status: Status::Ok,
+        size,
+        idle,
+        in_use: (size as usize).saturating_sub(idle),
+        max_connections,
+        saturated: size >= max_connections && idle == 0,
+    }
+}
+
+async fn migrations(pool: &PgPool) -> MigrationCheck {
+    let query = sqlx::query_scalar::<_, i64>(
+        "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+    )
+    .fetch_all(pool);
+
+    let applied: HashSet<i64> = match check_query(query).await {
+        Ok(applied) => applied.into_iter().collect(),
+        // The table doesn't exist until the first migration runs
+        Err(CheckError::QueryFailed(sqlx::Error::Database(e)))
+            if e.code().as_deref() == Some("42P01") =>
+        {
+            HashSet::new()
+        }
+        Err(e) => {
+            warn!("Readiness check couldn't read applied migrations: {e}");
+            return MigrationCheck {
+                status: Status::Fail,
+                pending: Vec::new(),
+                error: Some(e.reason()),
+            };
+        }
+    };
+
+    let pending: Vec<i64> = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect();
+
+    MigrationCheck {
+        status: if pending.is_empty() {
+            Status::Ok
+        } else {
+            Status::Fail
+        },
+        pending,
+        error: None,
+    }
 }
@@ -1,6 +1,2 @@
-use uuid::Uuid;
-
-#[tracing::instrument(level = "debug", fields(
-    request_id = %Uuid::new_v4()
-))]
+#[tracing::instrument(level = "debug")]
 pub async fn index() {}
@@ -0,0 +1,10 @@
+use axum::http::StatusCode;
+
+use crate::error::Problem;
+
+/// Fallback for routes that exist but don't handle the request's method.
+///
+/// [axum] adds the `Allow` header with the route's methods.
+pub async fn method_not_allowed() -> Problem {
+    Problem::new(StatusCode::METHOD_NOT_ALLOWED)
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::header, response::IntoResponse};
+use prometheus_client::{encoding::text, metrics::info::Info, registry::Registry};
+#[cfg(target_os = "linux")]
+use tracing::warn;
+
+use crate::{
+    error::AppError,
+    metrics::{pool::PoolMetrics, Metrics, CONTENT_TYPE},
+    state::Database,
+};
+
+/// Metrics in the OpenMetrics text format.
+///
+/// Served on admin listeners, or on public listeners if there aren't any admin listeners.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn prometheus_metrics(
+    State(metrics): State<Arc<Metrics>>,
+    State(db): State<Database>,
+) -> Result<impl IntoResponse, AppError> {
+    // Metrics that are read when scraped
+    let mut scrape = Registry::default();
+    scrape.register(
+        "fantasia_build",
+        "Version of Fantasia and the compiler that built it.",
+        Info::new([
+            ("version", env!("CARGO_PKG_VERSION")),
+            ("rustc", env!("FANTASIA_RUSTC_VERSION")),
+        ]),
+    );
+    scrape.register_collector(Box::new(PoolMetrics::new(db.pool)));
+
+    #[cfg(target_os = "linux")]
+    match crate::metrics::process::ProcessMetrics::collect() {
+        Ok(process) => scrape.register_collector(Box::new(process)),
+        Err(e) => warn!("Couldn't read process metrics: {e}"),
+    }
+
+    let mut out = String::new();
+    metrics
+        .encode(&mut out)
+        .and_then(|()| text::encode_registry(&mut out, &scrape))
+        .and_then(|()| text::encode_eof(&mut out))
+        .map_err(|e| AppError::Internal(e.into()))?;
+
+    Ok(([(header::CONTENT_TYPE, CONTENT_TYPE)], out))
+}
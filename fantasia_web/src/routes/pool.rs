@@ -0,0 +1,24 @@
+use axum::{extract::State, Json};
+use serde::Serialize;
+
+use crate::state::Database;
+
+/// Connection counts of the Postgres pool.
+#[derive(Serialize, Debug)]
+pub struct PoolStats {
+    /// Open connections, including idle ones.
+    pub size: u32,
+    /// Open connections not currently in use.
+    pub idle: usize,
+    pub max_connections: u32,
+}
+
+/// Postgres pool statistics for operators.
+#[tracing::instrument(level = "debug", skip(db))]
+pub async fn pool_stats(State(db): State<Database>) -> Json<PoolStats> {
+    Json(PoolStats {
+        size: db.pool.size(),
+        idle: db.pool.num_idle(),
+        max_connections: db.pool.options().get_max_connections(),
+    })
+}
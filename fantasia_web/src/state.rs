@@ -1,22 +1,77 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
 use axum::extract::FromRef;
-use sqlx::PgPool;
+use sqlx::{pool::PoolConnection, PgPool, Postgres};
+use tokio::time::Instant;
+
+use crate::metrics::Metrics;
 
 /// Complete app state.
 #[derive(Clone)]
 pub struct State {
-    pub pool: PgPool,
+    pub pool: SharedPool,
+    pub metrics: Arc<Metrics>,
 }
 
 /// Database app state.
 #[derive(Clone)]
 pub struct Database {
     pub pool: PgPool,
+    metrics: Arc<Metrics>,
+}
+
+impl Database {
+    /// Acquire a connection from the pool and record how long it took in
+    /// [Metrics::pool_acquire_wait].
+    pub async fn acquire(&self) -> Result<PoolConnection<Postgres>, sqlx::Error> {
+        let start = Instant::now();
+        let conn = self.pool.acquire().await;
+        self.metrics
+            .pool_acquire_wait
+            .observe(start.elapsed().as_secs_f64());
+
+        conn
+    }
+}
+
+/// Postgres pool that can be replaced while Fantasia is running.
+///
+/// [sqlx] can't resize a pool or change its timeouts, so new pool options are applied by
+/// swapping in a new pool. Requests that already hold the previous pool finish with it.
+#[derive(Clone, Debug)]
+pub struct SharedPool(Arc<RwLock<PgPool>>);
+
+impl SharedPool {
+    pub fn new(pool: PgPool) -> SharedPool {
+        SharedPool(Arc::new(RwLock::new(pool)))
+    }
+
+    /// Current pool.
+    pub fn get(&self) -> PgPool {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replace the pool and return the previous one so that the caller may close it.
+    pub fn replace(&self, pool: PgPool) -> PgPool {
+        let mut current = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, pool)
+    }
+}
+
+impl FromRef<State> for Arc<Metrics> {
+    fn from_ref(input: &State) -> Self {
+        input.metrics.clone()
+    }
 }
 
 impl FromRef<State> for Database {
     fn from_ref(input: &State) -> Self {
         Self {
-            pool: input.pool.clone(),
+            pool: input.pool.get(),
+            metrics: input.metrics.clone(),
         }
     }
 }
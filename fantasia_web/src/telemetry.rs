@@ -0,0 +1,72 @@
+//! Distributed tracing with OpenTelemetry.
+//!
+//! Spans are exported if an [OtlpExporter::layer] is installed in the global subscriber. Incoming
+//! requests continue the caller's trace from their W3C Trace Context headers. Requests to other
+//! services carry the current trace if their headers are passed to [inject].
+
+pub mod otlp;
+
+pub use otlp::{OtlpExporter, OtlpSettings};
+
+use axum::http::HeaderMap;
+use opentelemetry::{propagation::TextMapPropagator, Context};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Caller's trace from the `traceparent` and `tracestate` headers of a request.
+pub fn extract(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Add the current span's `traceparent` and `tracestate` to the headers of an outgoing request.
+///
+/// Nothing is added if spans aren't exported.
+pub fn inject(headers: &mut HeaderMap) {
+    let context = Span::current().context();
+    TraceContextPropagator::new().inject_context(&context, &mut HeaderInjector(headers));
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderMap;
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use opentelemetry_sdk::trace::TracerProvider;
+    use tracing::info_span;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::{extract, inject};
+
+    #[test]
+    fn injected_context_is_extracted() {
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("outgoing");
+            let _entered = span.enter();
+            let mut headers = HeaderMap::new();
+            inject(&mut headers);
+
+            let current = span.context();
+            let current = current.span().span_context().clone();
+            assert!(current.is_sampled());
+            assert!(headers.contains_key("traceparent"), "{headers:?}");
+
+            let extracted = extract(&headers);
+            let extracted = extracted.span().span_context().clone();
+            assert!(extracted.is_remote());
+            assert_eq!(
+                (current.trace_id(), current.span_id(), current.trace_flags()),
+                (
+                    extracted.trace_id(),
+                    extracted.span_id(),
+                    extracted.trace_flags()
+                )
+            );
+        });
+    }
+}
@@ -0,0 +1,194 @@
+//! Export of spans to an OpenTelemetry collector with OTLP over HTTP and JSON.
+//!
+//! [OtlpExporter::layer] records spans with [tracing_opentelemetry], and the exporter sends
+//! sampled spans to the collector in batches from a background task.
+
+use std::{collections::BTreeMap, io, time::Duration};
+
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    runtime,
+    trace::{Sampler, Tracer, TracerProvider},
+    Resource,
+};
+use serde::{de::Error as DeError, Deserialize, Deserializer};
+use tokio::task;
+use tracing::{warn, Subscriber};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+use url::Url;
+
+/// Time each request to the collector may take.
+pub const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Collector and resource settings for OTLP export.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpSettings {
+    /// Base URL of the collector's OTLP/HTTP receiver (e.g. `http://localhost:4318`). Spans are
+    /// posted to `/v1/traces` under it.
+    #[serde(deserialize_with = "deserialize_endpoint")]
+    pub endpoint: Url,
+    /// `service.name` of the exported resource.
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Share of new traces that are exported, between 0 and 1. Traces continued from a caller's
+    /// `traceparent` are exported if the caller sampled them.
+    #[serde(
+        default = "default_sampling_ratio",
+        deserialize_with = "deserialize_ratio"
+    )]
+    pub sampling_ratio: f64,
+    /// Extra resource attributes, such as `deployment.environment`.
+    #[serde(default)]
+    pub resource_attributes: BTreeMap<String, String>,
+}
+
+/// Sends spans recorded by its [OtlpExporter::layer] to the collector.
+///
+/// Call [OtlpExporter::shutdown] before exiting so that queued spans aren't lost.
+#[derive(Debug)]
+pub struct OtlpExporter {
+    provider: TracerProvider,
+}
+
+impl OtlpSettings {
+    pub fn new(endpoint: Url) -> Self {
+        OtlpSettings {
+            endpoint,
+            service_name: default_service_name(),
+            sampling_ratio: default_sampling_ratio(),
+            resource_attributes: BTreeMap::new(),
+        }
+    }
+
+    /// URL that spans are posted to.
+    pub fn traces_url(&self) -> Url {
+        let mut url = self.endpoint.clone();
+        let path = format!("{}/v1/traces", url.path().trim_end_matches('/'));
+        url.set_path(&path);
+        url
+    }
+
+    // Resource with the service name and extra attributes
+    fn resource(&self) -> Resource {
+        let attributes = self
+            .resource_attributes
+            .iter()
+            .map(|(key, value)| KeyValue::new(key.clone(), value.clone()));
+
+        Resource::new(
+            [KeyValue::new("service.name", self.service_name.clone())]
+                .into_iter()
+                .chain(attributes),
+        )
+    }
+}
+
+impl OtlpExporter {
+    /// Start an exporter. Must be called within a Tokio runtime.
+    pub fn new(settings: &OtlpSettings) -> io::Result<OtlpExporter> {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_protocol(Protocol::HttpJson)
+            .with_endpoint(settings.traces_url())
+            .with_timeout(EXPORT_TIMEOUT)
+            .build()
+            .map_err(io::Error::other)?;
+
+        // Traces continued from a caller keep the caller's decision
+        let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            settings.sampling_ratio,
+        )));
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_sampler(sampler)
+            .with_resource(settings.resource())
+            .build();
+
+        Ok(OtlpExporter { provider })
+    }
+
+    /// Layer that records spans for this exporter.
+    ///
+    /// Request spans fail if the response is a server error. Errors logged while handling the
+    /// request don't fail them.
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, Tracer>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer()
+            .with_tracer(self.provider.tracer("fantasia_web"))
+            .with_error_events_to_status(false)
+    }
+
+    /// Send queued spans and stop the exporter.
+    pub async fn shutdown(self) {
+        let provider = self.provider;
+
+        // Shutting down blocks until the batch task, which may run on this thread, is done
+        match task::spawn_blocking(move || provider.shutdown()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Couldn't send the last spans to the OTLP collector: {e}"),
+            Err(e) => warn!("Shutting down the OTLP exporter failed: {e}"),
+        }
+    }
+}
+
+fn default_service_name() -> String {
+    "fantasia".to_owned()
+}
+
+fn default_sampling_ratio() -> f64 {
+    1.0
+}
+
+fn deserialize_endpoint<'de, D>(deserializer: D) -> Result<Url, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let endpoint = String::deserialize(deserializer)?;
+    let url = Url::parse(&endpoint).map_err(|e| D::Error::custom(format!("{endpoint}: {e}")))?;
+
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(D::Error::custom(format!(
+            "Unsupported OTLP endpoint scheme `{scheme}`"
+        ))),
+    }
+}
+
+fn deserialize_ratio<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ratio = f64::deserialize(deserializer)?;
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err(D::Error::custom("`sampling_ratio` must be between 0 and 1"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::OtlpSettings;
+
+    #[test]
+    fn traces_are_posted_under_the_endpoint() {
+        for (endpoint, expected) in [
+            ("http://localhost:4318", "http://localhost:4318/v1/traces"),
+            ("http://localhost:4318/", "http://localhost:4318/v1/traces"),
+            (
+                "https://collector.example/otlp/",
+                "https://collector.example/otlp/v1/traces",
+            ),
+        ] {
+            let settings = OtlpSettings::new(Url::parse(endpoint).unwrap());
+            assert_eq!(expected, settings.traces_url().as_str());
+        }
+    }
+}
@@ -5,13 +5,13 @@ use secrecy::SecretString;
 use tracing::error;
 
 /// Command line arguments.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Args {
     /// Override config file
     pub conf: Option<PathBuf>,
-    /// Override Fantasia host
+    /// Override Fantasia host (replaces `[[fantasia.listen]]`)
     pub host: Option<String>,
-    /// Override Fantasia port
+    /// Override Fantasia port (replaces `[[fantasia.listen]]`)
     pub port: Option<u16>,
     /// Override Postgres superuser
     pub pguser: Option<String>,
@@ -1,15 +1,26 @@
 use std::{
+    collections::HashMap,
     env,
     fmt::{self, Debug},
     fs::File,
-    io::{BufReader, Read},
+    io::{self, BufReader, Read},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
-use fantasia_web::PgPoolOptions;
+use fantasia_web::{
+    app::{
+        shutdown::DEFAULT_GRACE_PERIOD, CorsSettings, HttpSettings, ListenerSettings, Protocol,
+        Role, TlsSettings,
+    },
+    extract::TrustedProxies,
+    telemetry::OtlpSettings,
+    PgPoolOptions,
+};
 use secrecy::{ExposeSecret, Secret, SecretString};
 use serde::{de::Error as DeError, Deserialize};
-use tracing::{info, trace};
+use tracing::{debug, info, trace};
+use tracing_subscriber::EnvFilter;
 
 use super::args::Args;
 
@@ -22,18 +33,45 @@ pub struct Config {
     /// Postgres server options.
     #[serde(default)]
     pub postgres: Postgres,
+    /// Logging options.
+    #[serde(default)]
+    pub logging: Logging,
+    /// Export spans to an OpenTelemetry collector. Disabled if unset.
+    #[serde(default)]
+    pub otlp: Option<OtlpSettings>,
 }
 
 /// General application options, such as the socket address for the server.
 #[derive(Deserialize, Debug, PartialEq, Eq)]
-#[serde(deny_unknown_fields)]
+#[serde(deny_unknown_fields, default)]
 pub struct Application {
     /// Host to bind for the application (e.g. `localhost`)
+    ///
+    /// Ignored if `listen` is set.
     pub host: String,
     /// `host`'s port
     pub port: u16,
     /// Override `.env` path. Defaults to `.env` otherwise.
     pub env_file: Option<PathBuf>,
+    /// Time to wait for in-flight requests to finish on shutdown.
+    #[serde(
+        deserialize_with = "super::pool_options::deserialize_duration",
+        alias = "grace_period_seconds"
+    )]
+    pub grace_period: Duration,
+    /// Serve HTTPS instead of HTTP on `host` if set.
+    ///
+    /// Also used by HTTPS listeners in `listen` that don't have their own TLS settings.
+    pub tls: Option<TlsSettings>,
+    /// Listeners with their own settings. Overrides `host` and `port`.
+    pub listen: Vec<ListenerSettings>,
+    /// Middleware settings for every route.
+    pub http: HttpSettings,
+    /// Cross-origin requests allowed on public listeners. Disabled if unset.
+    pub cors: Option<CorsSettings>,
+    /// Networks of reverse proxies whose `Forwarded`, `X-Forwarded-For`, and `X-Real-IP` headers
+    /// are trusted.
+    pub trusted_proxies: TrustedProxies,
 }
 
 /// Postgres connection options
@@ -55,6 +93,78 @@ pub struct Postgres {
     pub options: Option<PgPoolOptions>,
 }
 
+/// Logging options
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct Logging {
+    /// [EnvFilter] directives such as `info,sqlx=warn`. `RUST_LOG` overrides this if set.
+    pub filter: Option<String>,
+    /// How each log line is formatted, for both stdout and files.
+    pub format: LogFormat,
+    /// Write logs to stdout.
+    pub stdout: bool,
+    /// Write logs to rotated files. Disabled if unset.
+    pub file: Option<LogFile>,
+}
+
+/// How log lines are formatted.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One line per event with the fields of every span it's in.
+    #[default]
+    Full,
+    /// Shorter lines with the fields of the spans an event is in but not their names.
+    Compact,
+    /// Multiple lines per event for reading during development.
+    Pretty,
+    /// One JSON object per line with the fields of the event and its spans at the top level.
+    Json,
+}
+
+/// Log files in a directory that are rotated and pruned.
+///
+/// Daily logs are written to a file per day (e.g. `fantasia.2024-01-31.log`). Otherwise, logs are
+/// written to `{prefix}.log`, which is renamed with a timestamp when it's rotated by size.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LogFile {
+    /// Directory of the log files. Created if it doesn't exist.
+    pub directory: PathBuf,
+    #[serde(default = "default_log_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub rotation: Rotation,
+    /// Size in bytes at which files are rotated with [Rotation::Size].
+    #[serde(default = "default_log_max_size", alias = "max_size_bytes")]
+    pub max_size: u64,
+    /// Rotated files to keep. The oldest are deleted after each rotation, or none if `0`.
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+/// When log files are rotated.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    /// At midnight UTC.
+    #[default]
+    Daily,
+    /// Before a file exceeds [LogFile::max_size].
+    Size,
+    /// Never, so only one file is written.
+    Never,
+}
+
+/// Environment variables read by [Config::augment].
+///
+/// Unlike [dotenv], this doesn't modify the process environment so that the `.env` file may be
+/// read again when the config is reloaded.
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    vars: HashMap<String, String>,
+}
+
 /// View into the parameters used to build the Postgres database URL.
 pub struct DatabaseUrlView<'s> {
     pub user: &'s str,
@@ -87,10 +197,84 @@ impl Default for Application {
             host: "localhost".into(),
             port: 8000,
             env_file: None,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            tls: None,
+            listen: Vec::new(),
+            http: HttpSettings::default(),
+            cors: None,
+            trusted_proxies: TrustedProxies::default(),
         }
     }
 }
 
+impl Application {
+    /// Listeners to bind.
+    ///
+    /// This is `listen` if set or a single public listener on `host` and `port` otherwise.
+    /// HTTPS listeners without TLS settings inherit `tls`.
+    pub fn listeners(&self) -> Vec<ListenerSettings> {
+        if self.listen.is_empty() {
+            // IPv6 addresses need brackets to be followed by a port
+            let address = if self.host.contains(':') && !self.host.starts_with('[') {
+                format!("[{}]:{}", self.host, self.port)
+            } else {
+                format!("{}:{}", self.host, self.port)
+            };
+            let protocol = if self.tls.is_some() {
+                Protocol::Https
+            } else {
+                Protocol::Http
+            };
+
+            vec![ListenerSettings {
+                address,
+                protocol,
+                tls: self.tls.clone(),
+                role: Role::Public,
+                proxy_protocol: false,
+                mode: None,
+                owner: None,
+                group: None,
+                name: None,
+            }]
+        } else {
+            self.listen
+                .iter()
+                .cloned()
+                .map(|mut listener| {
+                    if listener.protocol == Protocol::Https && listener.tls.is_none() {
+                        listener.tls.clone_from(&self.tls);
+                    }
+                    listener
+                })
+                .collect()
+        }
+    }
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Self {
+            filter: None,
+            format: LogFormat::default(),
+            stdout: true,
+            file: None,
+        }
+    }
+}
+
+fn default_log_prefix() -> String {
+    "fantasia".into()
+}
+
+fn default_log_max_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
 impl Default for Postgres {
     fn default() -> Self {
         Self {
@@ -105,6 +289,11 @@ impl Default for Postgres {
 }
 
 impl Postgres {
+    /// Pool options or [sqlx]'s defaults if unset.
+    pub fn pool_options(&self) -> PgPoolOptions {
+        self.options.clone().unwrap_or_default()
+    }
+
     /// Create a syntactically valid Postgres database URL.
     ///
     /// Syntactically valid only means that the URL is properly formatted. The content may still be
@@ -158,10 +347,16 @@ impl Config {
     /// Update configurations with CLI options and Postgres environmental variables.
     ///
     /// CLI options override env vars which in turn override the config file.
-    #[tracing::instrument(skip(self))]
-    pub fn augment(&mut self, args: Args) {
+    ///
+    /// Overriding the host or port replaces any listeners in `[[fantasia.listen]]`.
+    #[tracing::instrument(skip(self, env))]
+    pub fn augment(&mut self, args: Args, env: &Env) {
         // Override loaded settings with CLI options and env vars
 
+        if args.host.is_some() || args.port.is_some() {
+            self.fantasia.listen.clear();
+        }
+
         if let Some(host) = args.host {
             self.fantasia.host = host;
         }
@@ -172,39 +367,176 @@ impl Config {
 
         if let Some(user) = args
             .pguser
-            .or_else(|| env::var("POSTGRES_USER").ok())
-            .or_else(|| env::var("PGUSER").ok())
+            .or_else(|| env.var("POSTGRES_USER"))
+            .or_else(|| env.var("PGUSER"))
         {
             self.postgres.user = user;
         }
 
         if let Some(pass) = args
             .pgpassword
-            .or_else(|| env::var("POSTGRES_PASSWORD").ok().map(SecretString::new))
-            .or_else(|| env::var("PGPASSWORD").ok().map(SecretString::new))
+            .or_else(|| env.var("POSTGRES_PASSWORD").map(SecretString::new))
+            .or_else(|| env.var("PGPASSWORD").map(SecretString::new))
         {
             self.postgres.password = pass;
         }
 
-        if let Some(host) = args.pghost.or_else(|| env::var("PGHOST").ok()) {
+        if let Some(host) = args.pghost.or_else(|| env.var("PGHOST")) {
             self.postgres.host = host;
         }
 
         if let Some(port) = args
             .pgport
-            .or_else(|| env::var("PGPORT").ok().and_then(|port| port.parse().ok()))
+            .or_else(|| env.var("PGPORT").and_then(|port| port.parse().ok()))
         {
             self.postgres.port = port;
         }
 
         if let Some(database) = args
             .pgdatabase
-            .or_else(|| env::var("POSTGRES_DB").ok())
-            .or_else(|| env::var("PGDATABASE").ok())
+            .or_else(|| env.var("POSTGRES_DB"))
+            .or_else(|| env.var("PGDATABASE"))
         {
             self.postgres.database = database;
         }
     }
+
+    /// Check settings that deserializing alone doesn't catch, such as socket owners and log
+    /// filters.
+    ///
+    /// TLS certificates aren't loaded so that a broken certificate doesn't block other settings
+    /// from reloading.
+    #[tracing::instrument(skip(self))]
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidInput, e);
+
+        for listener in self.fantasia.listeners() {
+            listener.unix_socket()?;
+        }
+
+        let options = self.postgres.pool_options();
+        if options.get_max_connections() == 0 {
+            return Err(invalid(
+                "`postgres.options.max_connections` must be at least 1".to_string(),
+            ));
+        }
+        if options.get_min_connections() > options.get_max_connections() {
+            return Err(invalid(
+                "`postgres.options.min_connections` exceeds `max_connections`".to_string(),
+            ));
+        }
+
+        if let Some(filter) = &self.logging.filter {
+            EnvFilter::builder()
+                .parse(filter)
+                .map_err(|e| invalid(format!("Invalid `logging.filter`: {e}")))?;
+        }
+        if let Some(file) = &self.logging.file {
+            if file.prefix.is_empty() || file.prefix.contains(std::path::is_separator) {
+                return Err(invalid(
+                    "`logging.file.prefix` must be a file name".to_string(),
+                ));
+            }
+            if file.max_size == 0 {
+                return Err(invalid(
+                    "`logging.file.max_size` must be at least 1".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Settings that differ in `new` but only take effect after a restart.
+    ///
+    /// Log filters, Postgres pool options, and request limits may change while Fantasia is running.
+    pub fn restart_required(&self, new: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+
+        if self.fantasia.listeners() != new.fantasia.listeners() {
+            changed.push("fantasia.listen");
+        }
+        if self.fantasia.grace_period != new.fantasia.grace_period {
+            changed.push("fantasia.grace_period_seconds");
+        }
+        // Request limits are applied on reload
+        let fixed_http = |http: &HttpSettings| HttpSettings {
+            request_timeout: Duration::ZERO,
+            rate_limit: None,
+            ..http.clone()
+        };
+        if fixed_http(&self.fantasia.http) != fixed_http(&new.fantasia.http) {
+            changed.push("fantasia.http");
+        }
+        if self.fantasia.cors != new.fantasia.cors {
+            changed.push("fantasia.cors");
+        }
+        if self.fantasia.trusted_proxies != new.fantasia.trusted_proxies {
+            changed.push("fantasia.trusted_proxies");
+        }
+        if self.logging.format != new.logging.format {
+            changed.push("logging.format");
+        }
+        if self.logging.stdout != new.logging.stdout {
+            changed.push("logging.stdout");
+        }
+        if self.logging.file != new.logging.file {
+            changed.push("logging.file");
+        }
+        if self.otlp != new.otlp {
+            changed.push("otlp");
+        }
+        if self.postgres.database_url().expose_secret()
+            != new.postgres.database_url().expose_secret()
+        {
+            changed.push("postgres");
+        }
+
+        changed
+    }
+}
+
+impl Env {
+    /// Snapshot of the process environment.
+    ///
+    /// Variables that aren't valid Unicode are skipped.
+    pub fn from_process() -> Env {
+        let vars = env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+
+        Env { vars }
+    }
+
+    /// Add variables from `env_file` or `.env` that aren't already set.
+    ///
+    /// Like [dotenv], a missing `.env` is ignored but an overridden path must exist.
+    #[tracing::instrument(skip(self))]
+    pub fn with_env_file(&self, env_file: Option<&Path>) -> Result<Env, dotenvy::Error> {
+        let iter = match env_file {
+            Some(path) => dotenvy::from_path_iter(path)?,
+            None => match dotenvy::dotenv_iter() {
+                Ok(iter) => iter,
+                Err(e) => {
+                    debug!("Not using .env: {e}");
+                    return Ok(self.clone());
+                }
+            },
+        };
+
+        let mut env = self.clone();
+        for var in iter {
+            let (key, value) = var?;
+            env.vars.entry(key).or_insert(value);
+        }
+
+        Ok(env)
+    }
+
+    /// Value of `key` if set.
+    pub fn var(&self, key: &str) -> Option<String> {
+        self.vars.get(key).cloned()
+    }
 }
 
 /// Load environment from a file or .env.
@@ -250,12 +582,24 @@ pub fn dotenv(env_file: Option<&Path>) -> Result<(), dotenvy::Error> {
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{path::Path, time::Duration};
 
+    use axum::http::{header, Method};
     use secrecy::ExposeSecret;
     use test_log::test;
 
-    use super::{dotenv, Application, Config, Postgres};
+    use fantasia_web::{
+        app::{
+            http::{CompressionLevel, NormalizePath},
+            security_headers::SecurityHeaders,
+            HttpSettings, Protocol, Role,
+        },
+        PgPoolOptions,
+    };
+
+    use super::{
+        dotenv, Application, Config, Env, LogFile, LogFormat, Logging, Postgres, Rotation,
+    };
     use crate::args::Args;
 
     #[test]
@@ -292,7 +636,7 @@ mod tests {
             pgdatabase: None,
         };
 
-        config.augment(args);
+        config.augment(args, &Env::from_process());
         let expected = Config {
             fantasia: Application {
                 port: 666,
@@ -303,6 +647,8 @@ mod tests {
                 password: "gaben".to_string().into(),
                 ..Default::default()
             },
+            logging: Logging::default(),
+            otlp: None,
         };
 
         assert_eq!(expected.fantasia, config.fantasia);
@@ -318,4 +664,297 @@ mod tests {
             config.postgres.options.is_none()
         );
     }
+
+    #[test]
+    fn listeners_default_to_host_and_port() {
+        let app = Application {
+            host: "::1".into(),
+            ..Default::default()
+        };
+        let listeners = app.listeners();
+
+        assert_eq!(1, listeners.len());
+        assert_eq!("[::1]:8000", listeners[0].address);
+        assert_eq!(Protocol::Http, listeners[0].protocol);
+        assert_eq!(Role::Public, listeners[0].role);
+    }
+
+    #[test]
+    fn https_listeners_inherit_tls() -> Result<(), toml::de::Error> {
+        let config: Config = toml::from_str(
+            r#"
+            [fantasia.tls]
+            cert_chain = "cert.pem"
+            key = "key.pem"
+
+            [[fantasia.listen]]
+            address = "0.0.0.0:8443"
+            protocol = "https"
+
+            [[fantasia.listen]]
+            address = "127.0.0.1:9000"
+            role = "admin"
+            "#,
+        )?;
+        let listeners = config.fantasia.listeners();
+
+        assert_eq!(2, listeners.len());
+        assert_eq!(config.fantasia.tls, listeners[0].tls);
+        assert_eq!(Role::Public, listeners[0].role);
+        assert_eq!(Protocol::Http, listeners[1].protocol);
+        assert_eq!(None, listeners[1].tls);
+        assert_eq!(Role::Admin, listeners[1].role);
+
+        Ok(())
+    }
+
+    #[test]
+    fn http_settings_default_to_previous_middleware() -> Result<(), toml::de::Error> {
+        let config: Config = toml::from_str(
+            r#"
+            [fantasia.http]
+            request_timeout_seconds = 0
+            normalize_path = "none"
+
+            [fantasia.http.compression]
+            br = false
+            gzip_level = 9
+            "#,
+        )?;
+        let http = config.fantasia.http;
+
+        assert_eq!(Duration::ZERO, http.request_timeout);
+        assert_eq!(HttpSettings::default().body_limit, http.body_limit);
+        assert_eq!(NormalizePath::None, http.normalize_path);
+        assert!(http.compression.gzip && !http.compression.br);
+        assert_eq!(CompressionLevel::Precise(9), http.compression.gzip_level);
+        assert_eq!(CompressionLevel::Default, http.compression.br_level);
+
+        let config: Config = toml::from_str("[fantasia.http.compression]\nbr_level = \"best\"")?;
+        assert_eq!(
+            CompressionLevel::Best,
+            config.fantasia.http.compression.br_level
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rate_limit_routes_have_their_own_limits() -> Result<(), toml::de::Error> {
+        let config: Config = toml::from_str(
+            r#"
+            [fantasia.http.rate_limit]
+            burst = 20
+            requests_per_minute = 60
+
+            [[fantasia.http.rate_limit.routes]]
+            prefix = "/auth"
+            burst = 5
+            requests_per_minute = 10
+            "#,
+        )?;
+        let rate_limit = config.fantasia.http.rate_limit.unwrap();
+
+        assert_eq!(20, rate_limit.limit.burst.get());
+        assert_eq!("/auth", rate_limit.routes[0].prefix);
+        assert_eq!(10, rate_limit.routes[0].limit.requests_per_minute.get());
+        assert!(Config::default().fantasia.http.rate_limit.is_none());
+
+        let zero: Result<Config, _> =
+            toml::from_str("[fantasia.http.rate_limit]\nburst = 0\nrequests_per_minute = 1");
+        assert!(zero.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_security_headers_are_omitted() -> Result<(), toml::de::Error> {
+        let config: Config = toml::from_str(
+            r#"
+            [fantasia.http.security_headers]
+            x_frame_options = ""
+            referrer_policy = "same-origin"
+            "#,
+        )?;
+        let headers = config.fantasia.http.security_headers;
+
+        assert_eq!(None, headers.x_frame_options);
+        assert_eq!(
+            Some("same-origin"),
+            headers
+                .referrer_policy
+                .as_ref()
+                .map(|value| value.to_str().unwrap())
+        );
+        assert_eq!(
+            SecurityHeaders::default().content_security_policy,
+            headers.content_security_policy
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn cors_settings() -> Result<(), toml::de::Error> {
+        let config: Config = toml::from_str(
+            r#"
+            [fantasia.cors]
+            allowed_origins = ["https://fantasia.example", "https://*.fantasia.example"]
+            allowed_methods = ["get", "DELETE"]
+            allowed_headers = ["Content-Type"]
+            max_age_seconds = 600
+            "#,
+        )?;
+        let cors = config.fantasia.cors.unwrap();
+
+        assert_eq!(2, cors.allowed_origins.len());
+        assert_eq!(vec![Method::GET, Method::DELETE], cors.allowed_methods);
+        assert_eq!(vec![header::CONTENT_TYPE], cors.allowed_headers);
+        assert!(!cors.allow_credentials);
+        assert_eq!(Duration::from_secs(600), cors.max_age);
+
+        let wildcard: Result<Config, _> =
+            toml::from_str("[fantasia.cors]\nallowed_origins = [\"*\"]");
+        assert!(wildcard.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn log_file_settings() -> Result<(), toml::de::Error> {
+        let config: Config = toml::from_str(
+            r#"
+            [logging]
+            format = "json"
+            stdout = false
+
+            [logging.file]
+            directory = "/var/log/fantasia"
+            rotation = "size"
+            max_size_bytes = 1048576
+            "#,
+        )?;
+        let file = config.logging.file.as_ref().unwrap();
+
+        assert_eq!(LogFormat::Json, config.logging.format);
+        assert!(!config.logging.stdout);
+        assert_eq!(Path::new("/var/log/fantasia"), file.directory);
+        assert_eq!("fantasia", file.prefix);
+        assert_eq!(Rotation::Size, file.rotation);
+        assert_eq!(1024 * 1024, file.max_size);
+        assert_eq!(7, file.max_files);
+        assert!(Config::default().logging.stdout);
+        assert_eq!(LogFormat::Full, Config::default().logging.format);
+
+        let nested = Config {
+            logging: Logging {
+                file: Some(LogFile {
+                    prefix: "logs/fantasia".into(),
+                    ..file.clone()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(nested.validate().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn otlp_settings() -> Result<(), toml::de::Error> {
+        let config: Config = toml::from_str(
+            r#"
+            [otlp]
+            endpoint = "http://localhost:4318"
+            sampling_ratio = 0.25
+
+            [otlp.resource_attributes]
+            "deployment.environment" = "staging"
+            "#,
+        )?;
+        let otlp = config.otlp.unwrap();
+
+        assert_eq!(
+            "http://localhost:4318/v1/traces",
+            otlp.traces_url().as_str()
+        );
+        assert_eq!("fantasia", otlp.service_name);
+        assert_eq!(0.25, otlp.sampling_ratio);
+        assert_eq!(
+            "staging",
+            otlp.resource_attributes["deployment.environment"]
+        );
+
+        for invalid in [
+            "[otlp]\nendpoint = \"localhost:4318\"",
+            "[otlp]\nendpoint = \"http://localhost:4318\"\nsampling_ratio = 1.5",
+        ] {
+            assert!(toml::from_str::<Config>(invalid).is_err(), "{invalid}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn env_file_does_not_override_process_env() -> Result<(), dotenvy::Error> {
+        let path = format!("{}/dev.env", env!("CARGO_MANIFEST_DIR"));
+        let process = Env {
+            vars: [("PGUSER".to_string(), "NotJosh".to_string())].into(),
+        };
+        let env = process.with_env_file(Some(Path::new(&path)))?;
+
+        assert_eq!(Some("NotJosh".to_string()), env.var("PGUSER"));
+        assert_eq!(Some("fantasiadev".to_string()), env.var("PGDATABASE"));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_rejects_invalid_settings() {
+        let empty_pool = Config {
+            postgres: Postgres {
+                options: Some(PgPoolOptions::new().max_connections(0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let bad_filter = Config {
+            logging: Logging {
+                filter: Some("info,sqlx=loud".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert!(Config::default().validate().is_ok());
+        assert!(empty_pool.validate().is_err());
+        assert!(bad_filter.validate().is_err());
+    }
+
+    #[test]
+    fn restart_required_ignores_runtime_settings() {
+        let old = Config::default();
+        let new = Config {
+            fantasia: Application {
+                port: 8080,
+                http: HttpSettings {
+                    request_timeout: Duration::from_secs(5),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            postgres: Postgres {
+                options: Some(PgPoolOptions::new().max_connections(20)),
+                ..Default::default()
+            },
+            logging: Logging {
+                filter: Some("debug".into()),
+                ..Default::default()
+            },
+            otlp: None,
+        };
+
+        assert!(old.restart_required(&Config::default()).is_empty());
+        assert_eq!(vec!["fantasia.listen"], old.restart_required(&new));
+    }
 }
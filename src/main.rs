@@ -1,61 +1,106 @@
 mod args;
 mod config;
 mod pool_options;
+mod reload;
+mod signals;
 mod telemetry;
 
-use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 use futures::future::join_all;
 use telemetry::logging;
 use tracing::info;
-// use tracing_log::LogTracer;
+use tracing_log::LogTracer;
 
 use args::Args;
-use config::Config;
-use fantasia_web::{app::FantasiaBuilder, PgPoolOptions};
+use config::{Config, Env};
+use fantasia_web::app::{panic, upgrade, Fantasia, FantasiaBuilder, Shutdown};
+use reload::{Handles, Reloader};
 
 #[tokio::main]
 #[tracing::instrument]
 async fn main() -> Result<()> {
-    logging().context("Failed to set a global logger")?;
+    let (log_filter, log_output) = logging().context("Failed to set a global logger")?;
+    // `sqlx` and `rspotify` log with `log`, which the log filter applies to like everything else
+    LogTracer::init().context("Failed to forward `log` records")?;
+    // Handlers that panic are logged with a backtrace if `RUST_BACKTRACE` is set
+    panic::install_hook();
 
     let args = Args::parse_args().context("Failed to parse arguments")?;
     let conf_path = args
         .conf
-        .as_deref()
-        .unwrap_or_else(|| Path::new("fantasia.toml"));
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("fantasia.toml"));
 
     info!("Loading settings");
-    let mut config = Config::from_path(conf_path).context("Could not load settings")?;
+    // Reloading reads `.env` again on top of the environment Fantasia was started with
+    let process_env = Env::from_process();
+    let mut config = Config::from_path(&conf_path).context("Could not load settings")?;
     config::dotenv(config.fantasia.env_file.as_deref()).context("Invalid .env file")?;
-    config.augment(args);
+    config.augment(args.clone(), &Env::from_process());
+    config.validate().context("Invalid settings")?;
+    log_filter
+        .set(config.logging.filter.as_deref())
+        .context("Failed to apply the log filter")?;
+    let telemetry = log_output
+        .start(&config.logging, config.otlp.as_ref())
+        .context("Failed to start logging")?;
     let db_url = config.postgres.database_url_view();
 
     info!("Building Fantasia instance");
-    let fantasia = FantasiaBuilder::new_from_addr(
-        (config.fantasia.host, config.fantasia.port),
-        PgPoolOptions::default(),
+    let mut fantasia = FantasiaBuilder::new_from_activation(
+        &config.fantasia.listeners(),
+        config.postgres.pool_options(),
         &db_url,
     )
     .await
-    .context("Failed to initialize Fantasia instance")?;
+    .context("Failed to initialize Fantasia instance")?
+    .grace_period(config.fantasia.grace_period)
+    .http(config.fantasia.http.clone())
+    .trusted_proxies(config.fantasia.trusted_proxies.clone());
+    if let Some(cors) = &config.fantasia.cors {
+        fantasia = fantasia.cors(cors.clone());
+    }
+    let pool = fantasia.pool().clone();
+    let handles = Handles {
+        log_filter,
+        pool: pool.clone(),
+        certs: fantasia.certificates().to_vec(),
+        limits: fantasia.limits().clone(),
+    };
+
+    let shutdown = Shutdown::new();
+    tokio::spawn(signals::shutdown_on_signal(shutdown.clone()));
+    let reloader = Reloader::new(conf_path, args, process_env, config, handles);
+    tokio::spawn(signals::reload_on_signal(reloader, shutdown.clone()));
 
     info!("Starting server");
-    let results = join_all(
-        fantasia
-            .into_server()
-            // .context("Failed to build Hyper server")?
-            .await
-            .into_iter()
-            .map(|sock_res| async {
-                sock_res
-                    .expect("Binding to a local socket should succeed")
-                    .server
-                    .await
-            }),
-    )
-    .await;
+    let (listeners, servers): (Vec<_>, Vec<_>) = fantasia
+        .into_server(&shutdown)
+        // .context("Failed to build Hyper server")?
+        .await
+        .into_iter()
+        .map(|sock_res| {
+            let Fantasia {
+                listener_fd,
+                server,
+                ..
+            } = sock_res.expect("Binding to a local socket should succeed");
+            (listener_fd, server)
+        })
+        .unzip();
+
+    // A previous process waits for this one to serve before draining
+    upgrade::notify_ready().context("Failed to notify the previous process")?;
+    tokio::spawn(signals::upgrade_on_signal(listeners, shutdown.clone()));
+
+    let results = join_all(servers).await;
+
+    info!("Closing Postgres pool");
+    pool.get().close().await;
+
+    telemetry.shutdown().await;
 
     for result in results {
         result.context("Spawned Fantasia instance crashed")?;
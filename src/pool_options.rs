@@ -16,39 +16,74 @@ use fantasia_web::PgPoolOptions;
     deny_unknown_fields
 )]
 pub struct PoolOptionsDef {
-    #[serde(getter = "PgPoolOptions::get_test_before_acquire", default)]
+    #[serde(
+        getter = "PgPoolOptions::get_test_before_acquire",
+        default = "PoolOptionsDef::default_test_before_acquire"
+    )]
     test_before_acquire: bool,
     #[serde(
         getter = "PgPoolOptions::get_acquire_timeout",
         deserialize_with = "deserialize_duration",
         alias = "acquire_timeout_seconds",
-        default
+        default = "PoolOptionsDef::default_acquire_timeout"
     )]
     acquire_timeout: Duration,
-    #[serde(getter = "PgPoolOptions::get_min_connections", default)]
+    #[serde(
+        getter = "PgPoolOptions::get_min_connections",
+        default = "PoolOptionsDef::default_min_connections"
+    )]
     min_connections: u32,
-    #[serde(getter = "PgPoolOptions::get_max_connections", default)]
+    #[serde(
+        getter = "PgPoolOptions::get_max_connections",
+        default = "PoolOptionsDef::default_max_connections"
+    )]
     max_connections: u32,
     #[serde(
         getter = "PgPoolOptions::get_max_lifetime",
         deserialize_with = "deserialize_duration",
         alias = "max_lifetime_seconds",
-        default
+        default = "PoolOptionsDef::default_max_lifetime"
     )]
     max_lifetime: Duration,
     #[serde(
         getter = "PgPoolOptions::get_idle_timeout",
         deserialize_with = "deserialize_duration",
         alias = "idle_timeout_seconds",
-        default
+        default = "PoolOptionsDef::default_idle_timeout"
     )]
     idle_timeout: Duration,
 }
 
+// Missing fields fall back to `sqlx`'s defaults rather than each type's default (e.g. zero
+// connections)
 impl PoolOptionsDef {
     fn default_pooloptions() -> PgPoolOptions {
         Self::default().into()
     }
+
+    fn default_test_before_acquire() -> bool {
+        Self::default().test_before_acquire
+    }
+
+    fn default_acquire_timeout() -> Duration {
+        Self::default().acquire_timeout
+    }
+
+    fn default_min_connections() -> u32 {
+        Self::default().min_connections
+    }
+
+    fn default_max_connections() -> u32 {
+        Self::default().max_connections
+    }
+
+    fn default_max_lifetime() -> Duration {
+        Self::default().max_lifetime
+    }
+
+    fn default_idle_timeout() -> Duration {
+        Self::default().idle_timeout
+    }
 }
 
 impl Default for PoolOptionsDef {
@@ -96,6 +131,11 @@ impl From<PgPoolOptions> for PoolOptionsDef {
     }
 }
 
+/// Whether `a` and `b` have the same options that [PoolOptionsDef] deserializes.
+pub(crate) fn same_options(a: &PgPoolOptions, b: &PgPoolOptions) -> bool {
+    PoolOptionsDef::from(a.clone()) == PoolOptionsDef::from(b.clone())
+}
+
 #[derive(Debug, Deserialize)]
 struct Helper(#[serde(with = "PoolOptionsDef")] PgPoolOptions);
 
@@ -110,8 +150,8 @@ where
     Ok(helper.map(|Helper(external)| external))
 }
 
-// Deserialize [std::time::Duration] from seconds
-fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+/// Deserialize [std::time::Duration] from seconds
+pub(crate) fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -0,0 +1,126 @@
+//! Reloading `fantasia.toml` and the `.env` file while Fantasia is running.
+
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result};
+use fantasia_web::{
+    app::{ReloadableCert, ReloadableLimits},
+    state::SharedPool,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    args::Args,
+    config::{Config, Env},
+    pool_options,
+    telemetry::LogFilter,
+};
+
+/// Settings that Fantasia is running with and what's needed to apply new ones.
+pub(crate) struct Reloader {
+    conf_path: PathBuf,
+    args: Args,
+    /// Process environment before `.env` was loaded.
+    env: Env,
+    config: Config,
+    handles: Handles,
+}
+
+/// Parts of the running server that reloaded settings are applied to.
+pub(crate) struct Handles {
+    pub(crate) log_filter: LogFilter,
+    pub(crate) pool: SharedPool,
+    pub(crate) certs: Vec<Arc<ReloadableCert>>,
+    pub(crate) limits: Arc<ReloadableLimits>,
+}
+
+impl Reloader {
+    /// `config` must be the settings that were loaded from `conf_path`, `args`, and `env`.
+    pub(crate) fn new(
+        conf_path: PathBuf,
+        args: Args,
+        env: Env,
+        config: Config,
+        handles: Handles,
+    ) -> Self {
+        Reloader {
+            conf_path,
+            args,
+            env,
+            config,
+            handles,
+        }
+    }
+
+    /// Load TLS certificates, the config file, and `.env` again and apply the settings that may
+    /// change at runtime.
+    ///
+    /// Each certificate is reloaded on its own and kept if its new files are broken. The current
+    /// settings are kept if the new ones can't be loaded, are invalid, or can't be applied.
+    /// Settings that require a restart are reported and otherwise ignored.
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn reload(&mut self) -> Result<()> {
+        for cert in &self.handles.certs {
+            if let Err(e) = cert.reload() {
+                error!("Keeping the current TLS certificate: {e}");
+            }
+        }
+
+        info!("Reloading settings from `{}`", self.conf_path.display());
+
+        let mut config = Config::from_path(&self.conf_path).context("Could not load settings")?;
+        let env = self
+            .env
+            .with_env_file(config.fantasia.env_file.as_deref())
+            .context("Invalid .env file")?;
+        config.augment(self.args.clone(), &env);
+        config.validate().context("Invalid settings")?;
+
+        for setting in self.config.restart_required(&config) {
+            warn!("`{setting}` changed; restart Fantasia to apply it");
+        }
+
+        // Everything that may fail happens before any setting is applied
+        let options = config.postgres.pool_options();
+        let pool = if pool_options::same_options(&self.config.postgres.pool_options(), &options) {
+            None
+        } else {
+            let connect_options = self.handles.pool.get().connect_options();
+            let pool = options
+                .connect_with((*connect_options).clone())
+                .await
+                .context("Failed to connect with the new pool options")?;
+            Some(pool)
+        };
+        if config.logging.filter != self.config.logging.filter {
+            self.handles
+                .log_filter
+                .set(config.logging.filter.as_deref())
+                .context("Failed to apply the log filter")?;
+            self.config.logging.filter = config.logging.filter;
+        }
+
+        let http = &mut self.config.fantasia.http;
+        let new_http = &config.fantasia.http;
+        if http.request_timeout != new_http.request_timeout
+            || http.rate_limit != new_http.rate_limit
+        {
+            info!("Applying new request limits");
+            self.handles.limits.reload(new_http);
+            http.request_timeout = new_http.request_timeout;
+            http.rate_limit.clone_from(&new_http.rate_limit);
+        }
+
+        if let Some(pool) = pool {
+            info!("Replacing the Postgres pool with new options");
+            let previous = self.handles.pool.replace(pool);
+            self.config.postgres.options = config.postgres.options;
+
+            // Requests that hold the previous pool finish with it
+            tokio::spawn(async move { previous.close().await });
+        }
+
+        info!("Reloaded settings");
+        Ok(())
+    }
+}
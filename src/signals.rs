@@ -0,0 +1,77 @@
+//! Process signal handling.
+
+use std::io;
+
+use fantasia_web::app::{upgrade, ListenerFd, Shutdown};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info};
+
+use crate::reload::Reloader;
+
+/// Trigger `shutdown` once the process receives SIGINT or SIGTERM.
+#[tracing::instrument(skip(shutdown))]
+pub(crate) async fn shutdown_on_signal(shutdown: Shutdown) -> io::Result<()> {
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => {
+            res?;
+            info!("Received SIGINT");
+        }
+        res = terminate() => {
+            res?;
+            info!("Received SIGTERM");
+        }
+    }
+
+    shutdown.trigger();
+    Ok(())
+}
+
+/// Hand `listeners` over to a new copy of the binary on SIGUSR2 and then trigger `shutdown`.
+///
+/// This process keeps serving if the upgrade fails. The listeners are released once `shutdown` is
+/// triggered so that they stop accepting connections.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn upgrade_on_signal(
+    listeners: Vec<ListenerFd>,
+    shutdown: Shutdown,
+) -> io::Result<()> {
+    let mut usr2 = signal(SignalKind::user_defined2())?;
+    loop {
+        tokio::select! {
+            _ = shutdown.triggered() => return Ok(()),
+            _ = usr2.recv() => info!("Received SIGUSR2"),
+        }
+
+        match upgrade::handoff(&listeners).await {
+            Ok(pid) => {
+                info!("Process {pid} took over the listeners; draining");
+                shutdown.trigger();
+                return Ok(());
+            }
+            Err(e) => error!("Upgrade failed; still serving: {e}"),
+        }
+    }
+}
+
+/// Reload settings with `reloader` on SIGHUP until `shutdown` is triggered.
+///
+/// Fantasia keeps its current settings if reloading fails.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn reload_on_signal(mut reloader: Reloader, shutdown: Shutdown) -> io::Result<()> {
+    let mut hup = signal(SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            _ = shutdown.triggered() => return Ok(()),
+            _ = hup.recv() => info!("Received SIGHUP"),
+        }
+
+        if let Err(e) = reloader.reload().await {
+            error!("Keeping the current settings: {e:#}");
+        }
+    }
+}
+
+async fn terminate() -> io::Result<()> {
+    signal(SignalKind::terminate())?.recv().await;
+    Ok(())
+}
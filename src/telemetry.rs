@@ -1,7 +1,167 @@
-use anyhow::Result;
+mod json;
+mod rolling;
 
-pub(crate) fn logging() -> Result<()> {
-    tracing_subscriber::fmt::init();
+use std::{env, io};
 
-    Ok(())
+use anyhow::{Context, Result};
+use fantasia_web::telemetry::{OtlpExporter, OtlpSettings};
+use tracing::{info, level_filters::LevelFilter, warn};
+use tracing_appender::non_blocking::{NonBlockingBuilder, WorkerGuard};
+use tracing_subscriber::{
+    filter::ParseError,
+    fmt::{self, format::JsonFields, MakeWriter},
+    layer::{Layered, SubscriberExt},
+    reload, EnvFilter, Layer, Registry,
+};
+
+use crate::config::{LogFormat, Logging};
+use json::FlatJson;
+
+/// Handle for replacing the global log filter at runtime.
+pub(crate) struct LogFilter(reload::Handle<EnvFilter, Registry>);
+
+/// Handle for replacing where logs and spans are sent once settings are loaded.
+pub(crate) struct LogOutput(reload::Handle<Output, Filtered>);
+
+/// Writers and exporters to flush before exiting.
+#[must_use = "Logs may be lost unless telemetry is shut down"]
+pub(crate) struct Telemetry {
+    guards: Vec<WorkerGuard>,
+    exporter: Option<OtlpExporter>,
+}
+
+type Filtered = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+type Output = Vec<Box<dyn Layer<Filtered> + Send + Sync>>;
+
+/// Install the global logger.
+///
+/// Logs are filtered by `RUST_LOG` or else only errors are logged until [LogFilter::set] is called
+/// with the configured filter. Logs are written to stdout until [LogOutput::start] is called.
+///
+/// Records from the `log` crate aren't seen unless `LogTracer` is installed too.
+pub(crate) fn logging() -> Result<(LogFilter, LogOutput)> {
+    let (filter, filter_handle) = reload::Layer::new(env_filter(None)?);
+    let output: Output = vec![fmt::layer().boxed()];
+    let (output, output_handle) = reload::Layer::new(output);
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::registry().with(filter).with(output),
+    )?;
+
+    Ok((LogFilter(filter_handle), LogOutput(output_handle)))
+}
+
+impl LogOutput {
+    /// Write logs to the sinks in `logging` and export spans with `otlp`.
+    ///
+    /// Shut [Telemetry] down before exiting to write the last logs and spans.
+    pub(crate) fn start(
+        &self,
+        logging: &Logging,
+        otlp: Option<&OtlpSettings>,
+    ) -> Result<Telemetry> {
+        let mut output: Output = Vec::new();
+        let mut guards = Vec::new();
+
+        if logging.stdout {
+            let (writer, guard) = non_blocking("stdout").finish(io::stdout());
+            output.push(fmt_layer(logging.format, writer, true));
+            guards.push(guard);
+        }
+
+        if let Some(file) = &logging.file {
+            let rolling = rolling::log_file(file).with_context(|| {
+                format!("Failed to open a log file in {}", file.directory.display())
+            })?;
+            let (writer, guard) = non_blocking("file").finish(rolling);
+            output.push(fmt_layer(logging.format, writer, false));
+            guards.push(guard);
+        }
+
+        let exporter = match otlp {
+            Some(settings) => {
+                let exporter = OtlpExporter::new(settings)?;
+                output.push(exporter.layer().boxed());
+                Some(exporter)
+            }
+            None => None,
+        };
+
+        self.0.reload(output)?;
+        if let Some(file) = &logging.file {
+            info!("Writing logs to {}", file.directory.display());
+        }
+        if let Some(settings) = otlp {
+            info!("Exporting spans to {}", settings.traces_url());
+        }
+
+        Ok(Telemetry { guards, exporter })
+    }
+}
+
+impl Telemetry {
+    /// Send the last spans and flush the logs written so far.
+    pub(crate) async fn shutdown(self) {
+        if let Some(exporter) = self.exporter {
+            info!("Sending the last spans to the OTLP collector");
+            exporter.shutdown().await;
+        }
+
+        // Writer threads finish their queues before they're joined
+        drop(self.guards);
+    }
+}
+
+impl LogFilter {
+    /// Filter logs with `directives` (e.g. `info,sqlx=warn`) unless `RUST_LOG` is set.
+    pub(crate) fn set(&self, directives: Option<&str>) -> Result<()> {
+        if directives.is_some() && env::var_os(EnvFilter::DEFAULT_ENV).is_some() {
+            warn!(
+                "`{}` overrides the configured log filter",
+                EnvFilter::DEFAULT_ENV
+            );
+        }
+
+        let filter = env_filter(directives)?;
+        info!("Log filter: {filter}");
+        self.0.reload(filter)?;
+
+        Ok(())
+    }
+}
+
+/// Writer thread for the sink `name`.
+///
+/// Logging blocks rather than dropping lines if the thread falls behind, so every line is written
+/// by the time the thread is shut down.
+fn non_blocking(name: &str) -> NonBlockingBuilder {
+    NonBlockingBuilder::default()
+        .lossy(false)
+        .thread_name(&format!("fantasia-log-{name}"))
+}
+
+/// Layer that writes logs in `format` with `writer`, with colors if `ansi` is set.
+fn fmt_layer<W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<Filtered> + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_ansi(ansi).with_writer(writer);
+    match format {
+        LogFormat::Full => layer.boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer
+            .fmt_fields(JsonFields::new())
+            .event_format(FlatJson)
+            .boxed(),
+    }
+}
+
+/// Parse `RUST_LOG` or else `directives`.
+pub(crate) fn env_filter(directives: Option<&str>) -> Result<EnvFilter, ParseError> {
+    let builder = EnvFilter::builder().with_default_directive(LevelFilter::ERROR.into());
+
+    match env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(rust_log) => builder.parse(rust_log),
+        Err(_) => builder.parse(directives.unwrap_or_default()),
+    }
 }
@@ -0,0 +1,176 @@
+//! JSON log lines for log pipelines.
+
+use std::fmt::{self, Debug};
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use tracing::{field::Field, Event, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::{
+    field::Visit,
+    fmt::{
+        format::{JsonFields, Writer},
+        FmtContext, FormatEvent, FormattedFields,
+    },
+    registry::LookupSpan,
+};
+
+/// Formats each event as one JSON object with the fields of its spans flattened into it.
+///
+/// Fields of inner spans override those of outer spans and the event's own fields override both,
+/// so e.g. `request_id` from the request span is on every line logged while handling it.
+/// `timestamp`, `level`, `target`, and `span` (the innermost span's name) are always set. Use
+/// with [JsonFields] so that span fields are recorded as JSON.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct FlatJson;
+
+impl<S> FormatEvent<S, JsonFields> for FlatJson
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut line = Map::new();
+
+        let mut innermost = None;
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                innermost = Some(span.name());
+                let extensions = span.extensions();
+                let fields = extensions
+                    .get::<FormattedFields<JsonFields>>()
+                    .and_then(|fields| serde_json::from_str::<Map<String, Value>>(fields).ok());
+                if let Some(fields) = fields {
+                    line.extend(fields);
+                }
+            }
+        }
+
+        event.record(&mut Fields(&mut line));
+
+        // Records from `log` carry their metadata as fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        line.insert(
+            "timestamp".into(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Micros, true)
+                .into(),
+        );
+        line.insert("level".into(), metadata.level().as_str().into());
+        line.insert("target".into(), metadata.target().into());
+        if let Some(name) = innermost {
+            line.insert("span".into(), name.into());
+        }
+
+        let line = serde_json::to_string(&line).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{line}")
+    }
+}
+
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl Fields<'_> {
+    fn insert(&mut self, field: &Field, value: Value) {
+        if !field.name().starts_with("log.") {
+            self.0.insert(field.name().to_owned(), value);
+        }
+    }
+}
+
+impl Visit for Fields<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.insert(field, value.to_string().into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.insert(field, format!("{value:?}").into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use serde_json::Value;
+    use tracing::{info, info_span, subscriber};
+    use tracing_subscriber::{
+        fmt::{self, format::JsonFields},
+        layer::SubscriberExt,
+    };
+
+    use super::FlatJson;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn span_fields_are_flattened() {
+        let output = Shared::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            fmt::layer()
+                .fmt_fields(JsonFields::new())
+                .event_format(FlatJson)
+                .with_writer(move || writer.clone()),
+        );
+
+        subscriber::with_default(subscriber, || {
+            let request = info_span!("request", request_id = "abc123", status = 0);
+            let _request = request.enter();
+            let _handler = info_span!("handler", user = "someone").entered();
+            request.record("status", 200);
+            info!(elapsed_ms = 5, "Finished");
+        });
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.trim_end()).unwrap();
+        assert_eq!("abc123", line["request_id"]);
+        assert_eq!(200, line["status"]);
+        assert_eq!("someone", line["user"]);
+        assert_eq!(5, line["elapsed_ms"]);
+        assert_eq!("Finished", line["message"]);
+        assert_eq!("INFO", line["level"]);
+        assert_eq!("handler", line["span"]);
+        assert_eq!(module_path!(), line["target"]);
+        assert!(line["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+}
@@ -0,0 +1,260 @@
+//! Log files that are rotated by date or size and pruned to a number of files.
+//!
+//! Daily rotation is [tracing_appender]'s. It doesn't rotate by size, so [SizeRollingFile] does.
+
+use std::{
+    cmp::Reverse,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use tracing_appender::rolling::{self, RollingFileAppender};
+
+use crate::config::{LogFile, Rotation};
+
+/// Timestamp of files rotated by size, which may be followed by `-{n}`.
+const SIZE_STAMP: &str = "%Y-%m-%dT%H-%M-%S%.3f";
+
+/// Writer for the log files in [LogFile::directory].
+///
+/// Logs are written to `{prefix}.{date}.log` with daily rotation and to `{prefix}.log` otherwise.
+pub(crate) fn log_file(settings: &LogFile) -> io::Result<Box<dyn Write + Send>> {
+    let rotation = match settings.rotation {
+        Rotation::Daily => rolling::Rotation::DAILY,
+        Rotation::Never => rolling::Rotation::NEVER,
+        Rotation::Size => return Ok(Box::new(SizeRollingFile::new(settings)?)),
+    };
+
+    // The appender counts the file being written
+    let max_files = match settings.max_files {
+        0 => 0,
+        rotated => rotated.saturating_add(1),
+    };
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(&settings.prefix)
+        .filename_suffix("log")
+        .max_log_files(max_files)
+        .build(&settings.directory)
+        .map_err(io::Error::other)?;
+
+    Ok(Box::new(appender))
+}
+
+/// Writer for `{prefix}.log` that's renamed to `{prefix}.{date}T{time}.log` before it exceeds
+/// [LogFile::max_size].
+#[derive(Debug)]
+pub(crate) struct SizeRollingFile {
+    settings: LogFile,
+    file: File,
+    size: u64,
+}
+
+impl SizeRollingFile {
+    pub(crate) fn new(settings: &LogFile) -> io::Result<SizeRollingFile> {
+        fs::create_dir_all(&settings.directory)?;
+        let file = open(&active_path(settings))?;
+        let size = file.metadata()?.len();
+
+        Ok(SizeRollingFile {
+            settings: settings.clone(),
+            file,
+            size,
+        })
+    }
+
+    fn write_at(&mut self, buf: &[u8], now: DateTime<Utc>) -> io::Result<usize> {
+        // A line larger than the limit gets a file to itself rather than being split
+        if self.size > 0 && self.size + buf.len() as u64 > self.settings.max_size {
+            self.rotate(now)?;
+        }
+
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        self.file.flush()?;
+
+        let stamp = now.format(SIZE_STAMP).to_string();
+        // Rotating twice in a millisecond shouldn't overwrite a file, and the suffix has to
+        // follow the newest one even if older ones were pruned
+        let time = NaiveDateTime::parse_from_str(&stamp, SIZE_STAMP).map_err(io::Error::other)?;
+        let rotated = match self
+            .rotated()?
+            .into_iter()
+            .filter(|&((rotated_at, _), _)| rotated_at == time)
+            .map(|((_, n), _)| n)
+            .max()
+        {
+            Some(n) => self.path(&format!("{stamp}-{}", n + 1)),
+            None => self.path(&stamp),
+        };
+
+        let active = active_path(&self.settings);
+        fs::rename(&active, rotated)?;
+        self.file = open(&active)?;
+        self.size = 0;
+
+        self.prune()
+    }
+
+    // Delete the oldest rotated files beyond `max_files`
+    fn prune(&self) -> io::Result<()> {
+        if self.settings.max_files == 0 {
+            return Ok(());
+        }
+
+        let mut rotated = self.rotated()?;
+        // Newest first
+        rotated.sort_by_key(|&(age, _)| Reverse(age));
+
+        for (_, path) in rotated.iter().skip(self.settings.max_files) {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    // Rotated files in the directory and when they were rotated
+    fn rotated(&self) -> io::Result<Vec<((NaiveDateTime, u32), PathBuf)>> {
+        Ok(fs::read_dir(&self.settings.directory)?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let age = self.rotated_at(&entry.file_name().to_string_lossy())?;
+                Some((age, entry.path()))
+            })
+            .collect())
+    }
+
+    // When a file was rotated and its `-{n}` suffix, or `None` if it isn't a rotated file
+    fn rotated_at(&self, name: &str) -> Option<(NaiveDateTime, u32)> {
+        let stamp = name
+            .strip_prefix(&self.settings.prefix)?
+            .strip_prefix('.')?
+            .strip_suffix(".log")?;
+
+        match NaiveDateTime::parse_from_str(stamp, SIZE_STAMP) {
+            Ok(time) => Some((time, 0)),
+            Err(_) => {
+                let (stamp, n) = stamp.rsplit_once('-')?;
+                let time = NaiveDateTime::parse_from_str(stamp, SIZE_STAMP).ok()?;
+                Some((time, n.parse().ok()?))
+            }
+        }
+    }
+
+    fn path(&self, stamp: &str) -> PathBuf {
+        self.settings
+            .directory
+            .join(format!("{}.{stamp}.log", self.settings.prefix))
+    }
+}
+
+impl Write for SizeRollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_at(buf, Utc::now())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn active_path(settings: &LogFile) -> PathBuf {
+    settings.directory.join(format!("{}.log", settings.prefix))
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write, path::Path};
+
+    use chrono::{DateTime, Duration, Utc};
+    use tempfile::TempDir;
+
+    use super::{log_file, SizeRollingFile};
+    use crate::config::{LogFile, Rotation};
+
+    fn settings(directory: &Path, rotation: Rotation) -> LogFile {
+        LogFile {
+            directory: directory.to_owned(),
+            prefix: "fantasia".into(),
+            rotation,
+            max_size: 10,
+            max_files: 2,
+        }
+    }
+
+    fn files(directory: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn daily_files_are_dated() {
+        let directory = TempDir::new().unwrap();
+        let mut file = log_file(&settings(directory.path(), Rotation::Daily)).unwrap();
+
+        file.write_all(b"first\n").unwrap();
+        file.flush().unwrap();
+
+        let today = Utc::now().format("%Y-%m-%d");
+        assert_eq!(
+            vec![format!("fantasia.{today}.log")],
+            files(directory.path())
+        );
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_max_files() {
+        let directory = TempDir::new().unwrap();
+        let mut file = SizeRollingFile::new(&settings(directory.path(), Rotation::Size)).unwrap();
+        let start: DateTime<Utc> = "2024-01-31T12:00:00Z".parse().unwrap();
+
+        for i in 0..5 {
+            file.write_at(b"12345678\n", start + Duration::seconds(i))
+                .unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                "fantasia.2024-01-31T12-00-03.000.log",
+                "fantasia.2024-01-31T12-00-04.000.log",
+                "fantasia.log"
+            ],
+            files(directory.path())
+        );
+    }
+
+    #[test]
+    fn files_rotated_in_the_same_millisecond_are_pruned_in_order() {
+        let directory = TempDir::new().unwrap();
+        let mut file = SizeRollingFile::new(&settings(directory.path(), Rotation::Size)).unwrap();
+        let now: DateTime<Utc> = "2024-01-31T12:00:00Z".parse().unwrap();
+
+        // `-1` sorts before `.` and `-10` before `-2` by name
+        for _ in 0..12 {
+            file.write_at(b"12345678\n", now).unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                "fantasia.2024-01-31T12-00-00.000-10.log",
+                "fantasia.2024-01-31T12-00-00.000-9.log",
+                "fantasia.log"
+            ],
+            files(directory.path())
+        );
+    }
+}
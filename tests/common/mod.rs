@@ -1,12 +1,112 @@
-use std::{env, io, time::Duration};
+// Each integration test only uses a subset of these helpers
+#![allow(dead_code)]
 
-use reqwest::Client;
+use std::{
+    env, fs,
+    io::{self, Write},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use futures::future::join_all;
+use reqwest::{Certificate, Client, ClientBuilder};
 use sqlx::PgPool;
-use tracing::info;
+use tempfile::TempDir;
+use tokio::task::JoinHandle;
+use tracing::{info, level_filters::LevelFilter, subscriber::DefaultGuard};
 
-use fantasia_web::app::{Fantasia, FantasiaBuilder};
+use fantasia_web::app::{
+    Fantasia, FantasiaBuilder, ListenAddr, Protocol, Role, Shutdown, TlsSettings, TlsVersion,
+};
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Fantasia instances spawned for a test.
+pub struct TestApp {
+    /// Bound local address, protocol, and role of each instance.
+    pub listeners: Vec<(ListenAddr, Protocol, Role)>,
+    shutdown: Shutdown,
+    servers: JoinHandle<Vec<io::Result<()>>>,
+}
+
+impl TestApp {
+    /// Endpoint URLs for `path` on every spawned instance.
+    pub fn endpoints(&self, path: &str) -> Vec<String> {
+        self.listeners
+            .iter()
+            .map(|(addr, protocol, _)| format!("{protocol}://{addr}{path}"))
+            .collect()
+    }
+
+    /// Endpoint URLs for `path` on instances with `role`.
+    pub fn role_endpoints(&self, role: Role, path: &str) -> Vec<String> {
+        self.listeners
+            .iter()
+            .filter(|(_, _, listener_role)| *listener_role == role)
+            .map(|(addr, protocol, _)| format!("{protocol}://{addr}{path}"))
+            .collect()
+    }
+
+    /// Handle that stops every instance without waiting for them to drain.
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Stop every instance and wait for them to drain.
+    #[tracing::instrument(skip(self))]
+    pub async fn stop(self) {
+        self.shutdown.trigger();
+
+        for result in self
+            .servers
+            .await
+            .expect("Spawned Fantasia instances should not panic")
+        {
+            result.expect("Fantasia instances should shut down cleanly");
+        }
+    }
+}
+
+/// Tracing output captured on the current thread.
+///
+/// Spawned servers run on the test's thread, so their spans and events are captured as well.
+#[derive(Clone, Default)]
+pub struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    /// Capture every level of tracing output until the guard is dropped.
+    pub fn start() -> (CapturedLogs, DefaultGuard) {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(LevelFilter::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        (logs, tracing::subscriber::set_default(subscriber))
+    }
+
+    pub fn contents(&self) -> String {
+        let logs = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        String::from_utf8_lossy(&logs).into_owned()
+    }
+}
+
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
 
 // Default user agent
 fn user_agent() -> String {
@@ -19,28 +119,133 @@ fn user_agent() -> String {
     )
 }
 
-#[tracing::instrument(skip(pool))]
-pub async fn spawn(pool: PgPool) -> Vec<io::Result<Fantasia>> {
-    info!("Spawning server for tests");
+/// Self-signed certificate for `localhost` and `127.0.0.1`.
+///
+/// The PEM files live in a temporary directory which is deleted when [TestCert] is dropped.
+pub struct TestCert {
+    pub settings: TlsSettings,
+    pub pem: String,
+    _dir: TempDir,
+}
+
+impl TestCert {
+    #[tracing::instrument]
+    pub fn generate() -> TestCert {
+        let dir = tempfile::tempdir().expect("Creating a temporary directory should succeed");
+        let settings = TlsSettings {
+            cert_chain: dir.path().join("cert.pem"),
+            key: dir.path().join("key.pem"),
+            min_version: TlsVersion::default(),
+        };
+        let pem = write_cert(&settings);
+
+        TestCert {
+            settings,
+            pem,
+            _dir: dir,
+        }
+    }
+
+    /// Replace the certificate and key files with a new pair.
+    pub fn rotate(&mut self) {
+        self.pem = write_cert(&self.settings);
+    }
+}
 
-    // Bind to any port. This is useful for running multiple apps concurrently for tests
+// Write a new self-signed certificate and key to the paths in `settings`
+fn write_cert(settings: &TlsSettings) -> String {
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(["localhost".into(), "127.0.0.1".into()])
+            .expect("Generating a self-signed certificate should succeed");
+
+    fs::write(&settings.cert_chain, cert.pem()).expect("Writing the certificate should succeed");
+    fs::write(&settings.key, key_pair.serialize_pem()).expect("Writing the key should succeed");
+
+    cert.pem()
+}
+
+// Bind to any port. This is useful for running multiple apps concurrently for tests
+pub fn test_builder(pool: PgPool) -> FantasiaBuilder {
     let sockets = ["127.0.0.1:0"
         .parse()
         .expect("`127.0.0.1:0` is a valid address")];
 
-    let fantasia = FantasiaBuilder::new(&sockets, pool);
-    fantasia.into_server().await
-    // .expect("Building a `Server` from a valid `Fantasia` struct should succeed")
+    FantasiaBuilder::new(&sockets, pool).grace_period(GRACE_PERIOD)
 }
 
-#[tracing::instrument]
-pub fn test_client() -> reqwest::Result<Client> {
+#[tracing::instrument(skip(pool))]
+pub async fn spawn(pool: PgPool) -> TestApp {
+    info!("Spawning server for tests");
+    spawn_builder(test_builder(pool)).await
+}
+
+#[tracing::instrument(skip(pool, cert))]
+pub async fn spawn_tls(pool: PgPool, cert: &TestCert) -> TestApp {
+    info!("Spawning HTTPS server for tests");
+
+    let acceptor = cert
+        .settings
+        .acceptor()
+        .expect("Loading a generated certificate should succeed");
+    spawn_builder(test_builder(pool).tls(acceptor)).await
+}
+
+pub async fn spawn_builder(builder: FantasiaBuilder) -> TestApp {
+    let shutdown = Shutdown::new();
+    let (listeners, servers): (Vec<_>, Vec<_>) = builder
+        .into_server(&shutdown)
+        .await
+        .into_iter()
+        .map(|sock_res| {
+            let Fantasia {
+                sock_addr,
+                protocol,
+                role,
+                server,
+                ..
+            } = sock_res.expect("Binding to a local socket for tests should succeed.");
+            ((sock_addr, protocol, role), server)
+        })
+        .unzip();
+
+    if servers.is_empty() {
+        panic!("Expected at least one spawned Fantasia instance");
+    }
+
+    TestApp {
+        listeners,
+        shutdown,
+        servers: tokio::spawn(join_all(servers)),
+    }
+}
+
+fn test_client_builder() -> ClientBuilder {
     Client::builder()
         .user_agent(user_agent())
         .timeout(DEFAULT_TIMEOUT)
         .connect_timeout(DEFAULT_TIMEOUT)
         .connection_verbose(true)
         .use_rustls_tls()
-        .trust_dns(true)
-        .build()
+        .hickory_dns(true)
+}
+
+#[tracing::instrument]
+pub fn test_client() -> reqwest::Result<Client> {
+    test_client_builder().build()
+}
+
+/// Client that trusts `cert`.
+///
+/// HTTP/2 is negotiated via ALPN unless `http1_only` is set.
+#[tracing::instrument(skip(cert))]
+pub fn test_tls_client(cert: &TestCert, http1_only: bool) -> reqwest::Result<Client> {
+    let builder = test_client_builder()
+        .tls_built_in_root_certs(false)
+        .add_root_certificate(Certificate::from_pem(cert.pem.as_bytes())?);
+
+    if http1_only {
+        builder.http1_only().build()
+    } else {
+        builder.build()
+    }
 }
@@ -0,0 +1,118 @@
+mod common;
+
+use std::time::Duration;
+
+use reqwest::{header, Method, StatusCode};
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{spawn_builder, test_builder, test_client, TestApp};
+use fantasia_web::app::{cors::AllowedOrigin, CorsSettings};
+
+async fn spawn_cors(pool: PgPool) -> TestApp {
+    let origins = ["https://fantasia.example", "https://*.fantasia.example"]
+        .into_iter()
+        .map(|origin| AllowedOrigin::try_from(origin.to_owned()).unwrap())
+        .collect();
+    let cors = CorsSettings {
+        allowed_headers: vec![axum::http::header::CONTENT_TYPE],
+        allow_credentials: true,
+        max_age: Duration::from_secs(600),
+        ..CorsSettings::new(origins)
+    };
+
+    spawn_builder(test_builder(pool).cors(cors)).await
+}
+
+async fn preflight(app: &TestApp, origin: &str) -> reqwest::Response {
+    test_client()
+        .expect("Should be able to build an HTTP client")
+        .request(
+            Method::OPTIONS,
+            app.endpoints("/health_check").pop().unwrap(),
+        )
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn preflight_from_allowed_origins(pool: PgPool) {
+    let app = spawn_cors(pool).await;
+
+    for origin in ["https://fantasia.example", "https://app.fantasia.example"] {
+        let response = preflight(&app, origin).await;
+        let headers = response.headers();
+
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(origin, headers[header::ACCESS_CONTROL_ALLOW_ORIGIN]);
+        assert_eq!("true", headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS]);
+        assert_eq!(
+            "GET,HEAD,POST",
+            headers[header::ACCESS_CONTROL_ALLOW_METHODS]
+        );
+        assert_eq!(
+            "content-type",
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+        );
+        assert_eq!("600", headers[header::ACCESS_CONTROL_MAX_AGE]);
+        assert!(headers
+            .get_all(header::VARY)
+            .iter()
+            .any(|vary| vary.to_str().unwrap().contains("origin")));
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn unknown_origins_get_no_cors_headers(pool: PgPool) {
+    let app = spawn_cors(pool).await;
+
+    // Browsers reject responses without `Access-Control-Allow-Origin`
+    for origin in [
+        "https://evil.example",
+        "http://fantasia.example",
+        "https://fantasia.example.evil.example",
+        "https://evilfantasia.example",
+    ] {
+        let response = preflight(&app, origin).await;
+
+        assert!(
+            response
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none(),
+            "{origin} shouldn't be allowed"
+        );
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn simple_requests_from_allowed_origins(pool: PgPool) {
+    let app = spawn_cors(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let response = client
+        .get(app.endpoints("/health_check").pop().unwrap())
+        .header(header::ORIGIN, "https://fantasia.example")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        "https://fantasia.example",
+        response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN]
+    );
+
+    app.stop().await;
+}
@@ -1,40 +1,20 @@
 mod common;
 
-use std::future::IntoFuture;
-
-use futures::future::join_all;
 use reqwest::StatusCode;
+use serde_json::Value;
 use sqlx::PgPool;
 use test_log::test;
 use tracing::info;
 
 use common::{spawn, test_client};
-use fantasia_web::app::Fantasia;
 
 #[tracing::instrument(skip(pool))]
 #[test(sqlx::test)]
 async fn health_check_works(pool: PgPool) {
-    let (endpoints, servers): (Vec<_>, Vec<_>) = spawn(pool)
-        .await
-        .into_iter()
-        .map(|sock_res| {
-            let Fantasia { sock_addr, server } =
-                sock_res.expect("Binding to a local socket for tests should succeed.");
-            let endpoint = format!("http://{}/health_check", sock_addr);
-            (endpoint, server)
-        })
-        .unzip();
-
-    // Spawn servers
-    if servers.is_empty() {
-        panic!("Expected at least one spawned Fantasia instance");
-    }
-    let servers = join_all(servers.into_iter().map(IntoFuture::into_future));
-    let _handle = tokio::spawn(servers);
-
+    let app = spawn(pool).await;
     let client = test_client().expect("Should be able to build an HTTP client");
 
-    for endpoint in endpoints {
+    for endpoint in app.endpoints("/health_check") {
         info!("Sending a GET request to {endpoint}");
         let response =
             client.get(&*endpoint).send().await.unwrap_or_else(|e| {
@@ -42,4 +22,66 @@ async fn health_check_works(pool: PgPool) {
             });
         assert_eq!(StatusCode::OK, response.status());
     }
+
+    app.stop().await;
+}
+
+#[test(sqlx::test)]
+async fn liveness_probe_works(pool: PgPool) {
+    let app = spawn(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    for endpoint in app.endpoints("/health/live") {
+        let response = client.get(&*endpoint).send().await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        let body: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!("ok", body["status"]);
+    }
+
+    app.stop().await;
+}
+
+#[test(sqlx::test)]
+async fn readiness_probe_reports_dependencies(pool: PgPool) {
+    let app = spawn(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    for endpoint in app.endpoints("/health/ready") {
+        let response = client.get(&*endpoint).send().await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        let body: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!("ok", body["status"], "{body}");
+        assert_eq!("ok", body["postgres"]["status"], "{body}");
+        assert_eq!("ok", body["pool"]["status"], "{body}");
+        // `sqlx::test` applies every migration
+        assert_eq!(Some(&Vec::new()), body["migrations"]["pending"].as_array());
+    }
+
+    app.stop().await;
+}
+
+#[test(sqlx::test)]
+async fn readiness_probe_fails_without_postgres(pool: PgPool) {
+    let app = spawn(pool.clone()).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    pool.close().await;
+
+    for endpoint in app.endpoints("/health/ready") {
+        let response = client.get(&*endpoint).send().await.unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, response.status());
+
+        let body: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!("fail", body["status"], "{body}");
+        assert_eq!("fail", body["postgres"]["status"], "{body}");
+    }
+
+    // Liveness doesn't depend on Postgres
+    for endpoint in app.endpoints("/health/live") {
+        let response = client.get(&*endpoint).send().await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    app.stop().await;
 }
@@ -0,0 +1,248 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use reqwest::{header, StatusCode};
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{spawn_builder, test_builder, test_client, TestApp};
+use fantasia_web::app::{
+    http::{CompressionLevel, CompressionSettings, NormalizePath},
+    FantasiaBuilder, HttpSettings, Role,
+};
+
+// Admin listener with routes that exercise the middleware
+async fn spawn_http(pool: PgPool, http: HttpSettings) -> TestApp {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let routes = Router::new()
+        .route("/large", get(|| async { "fantasia ".repeat(100) }))
+        .route("/echo", post(|body: String| async move { body }))
+        .route(
+            "/slow",
+            get(|| async { tokio::time::sleep(Duration::from_secs(5)).await }),
+        );
+
+    let builder: FantasiaBuilder = test_builder(pool)
+        .listener(any_port, None, Role::Admin)
+        .admin_routes(routes)
+        .http(http);
+    spawn_builder(builder).await
+}
+
+fn admin_endpoint(app: &TestApp, path: &str) -> String {
+    app.role_endpoints(Role::Admin, path)
+        .pop()
+        .expect("An admin listener should be spawned")
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn compression_follows_settings(pool: PgPool) {
+    let http = HttpSettings {
+        compression: CompressionSettings {
+            br: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let app = spawn_http(pool, http).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let endpoint = admin_endpoint(&app, "/large");
+
+    for (accept, expected) in [("gzip", Some("gzip")), ("br", None)] {
+        let response = client
+            .get(&endpoint)
+            .header(header::ACCEPT_ENCODING, accept)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(
+            expected,
+            response
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|encoding| encoding.to_str().unwrap()),
+            "Accept-Encoding: {accept}"
+        );
+    }
+
+    // Request bodies are only decompressed with enabled algorithms
+    let response = client
+        .post(admin_endpoint(&app, "/echo"))
+        .header(header::CONTENT_ENCODING, "br")
+        .body("fantasia")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, response.status());
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn algorithms_have_their_own_levels(pool: PgPool) {
+    let http = HttpSettings {
+        compression: CompressionSettings {
+            // Level 0 stores the body without compressing it
+            gzip_level: CompressionLevel::Precise(0),
+            deflate_level: CompressionLevel::Best,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let app = spawn_http(pool, http).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let endpoint = admin_endpoint(&app, "/large");
+    let uncompressed = "fantasia ".repeat(100).len();
+
+    let mut sizes = Vec::new();
+    for accept in ["gzip", "deflate", "gzip;q=0.5, deflate;q=0.9"] {
+        let response = client
+            .get(&endpoint)
+            .header(header::ACCEPT_ENCODING, accept)
+            .send()
+            .await
+            .unwrap();
+        let encoding = response.headers()[header::CONTENT_ENCODING].clone();
+        sizes.push((encoding, response.bytes().await.unwrap().len()));
+    }
+
+    assert_eq!("gzip", sizes[0].0);
+    assert!(sizes[0].1 > uncompressed);
+    assert_eq!("deflate", sizes[1].0);
+    assert!(sizes[1].1 < uncompressed / 10);
+    // The client's preference wins over the order of the layers
+    assert_eq!(sizes[1], sizes[2]);
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn small_responses_are_not_compressed(pool: PgPool) {
+    let http = HttpSettings {
+        compression: CompressionSettings {
+            min_size: 4096,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let app = spawn_http(pool, http).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let response = client
+        .get(admin_endpoint(&app, "/large"))
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn request_limits_follow_settings(pool: PgPool) {
+    let http = HttpSettings {
+        request_timeout: Duration::from_millis(200),
+        body_limit: 16,
+        ..Default::default()
+    };
+    let app = spawn_http(pool, http).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let response = client
+        .get(admin_endpoint(&app, "/slow"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::REQUEST_TIMEOUT, response.status());
+
+    let endpoint = admin_endpoint(&app, "/echo");
+    let response = client
+        .post(&endpoint)
+        .body("fantasia")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let response = client
+        .post(&endpoint)
+        .body("fantasia ".repeat(4))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, response.status());
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn reloaded_timeouts_apply_to_new_requests(pool: PgPool) {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let routes = Router::new().route(
+        "/slow",
+        get(|| async { tokio::time::sleep(Duration::from_millis(300)).await }),
+    );
+    let builder = test_builder(pool)
+        .listener(any_port, None, Role::Admin)
+        .admin_routes(routes)
+        .http(HttpSettings {
+            request_timeout: Duration::from_millis(100),
+            ..Default::default()
+        });
+    let limits = builder.limits().clone();
+    let app = spawn_builder(builder).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let endpoint = admin_endpoint(&app, "/slow");
+
+    let response = client.get(&endpoint).send().await.unwrap();
+    assert_eq!(StatusCode::REQUEST_TIMEOUT, response.status());
+
+    limits.reload(&HttpSettings {
+        request_timeout: Duration::from_secs(5),
+        ..Default::default()
+    });
+    let response = client.get(&endpoint).send().await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn trailing_slashes_follow_settings(pool: PgPool) {
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    for (normalize_path, expected) in [
+        (NormalizePath::TrimTrailingSlash, StatusCode::OK),
+        (NormalizePath::None, StatusCode::NOT_FOUND),
+    ] {
+        let http = HttpSettings {
+            normalize_path,
+            ..Default::default()
+        };
+        let app = spawn_builder(test_builder(pool.clone()).http(http)).await;
+
+        for endpoint in app.endpoints("/health_check/") {
+            let response = client.get(&endpoint).send().await.unwrap();
+            assert_eq!(expected, response.status(), "{normalize_path:?}");
+        }
+
+        app.stop().await;
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use axum::{routing::get, Router};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use test_log::test;
+use tracing::info;
+
+use common::{spawn_builder, test_builder, test_client, test_tls_client, TestCert};
+use fantasia_web::app::{Protocol, Role};
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn listeners_use_their_own_settings(pool: PgPool) {
+    let cert = TestCert::generate();
+    let acceptor = cert
+        .settings
+        .acceptor()
+        .expect("Loading a generated certificate should succeed");
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+
+    let app =
+        spawn_builder(test_builder(pool).listener(any_port, Some(acceptor), Role::Admin)).await;
+
+    let protocols: Vec<_> = app
+        .listeners
+        .iter()
+        .map(|&(_, protocol, role)| (protocol, role))
+        .collect();
+    assert_eq!(
+        vec![
+            (Protocol::Http, Role::Public),
+            (Protocol::Https, Role::Admin)
+        ],
+        protocols
+    );
+
+    // The client trusts the test certificate and can still talk plain HTTP
+    let client = test_tls_client(&cert, false).expect("Should be able to build an HTTPS client");
+    for endpoint in app.endpoints("/health_check") {
+        info!("Sending a GET request to {endpoint}");
+        let response =
+            client.get(&*endpoint).send().await.unwrap_or_else(|e| {
+                panic!("Should be able to send a GET request ({endpoint})\n\r{e}")
+            });
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn admin_routes_are_only_served_on_admin_listeners(pool: PgPool) {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let maintenance = Router::new().route("/maintenance", get(|| async { "off" }));
+
+    let app = spawn_builder(
+        test_builder(pool)
+            .listener(any_port, None, Role::Admin)
+            .admin_routes(maintenance),
+    )
+    .await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    for (path, public, admin) in [
+        ("/", StatusCode::OK, StatusCode::NOT_FOUND),
+        ("/health_check", StatusCode::OK, StatusCode::OK),
+        ("/pool", StatusCode::NOT_FOUND, StatusCode::OK),
+        ("/maintenance", StatusCode::NOT_FOUND, StatusCode::OK),
+    ] {
+        for (role, expected) in [(Role::Public, public), (Role::Admin, admin)] {
+            for endpoint in app.role_endpoints(role, path) {
+                info!("Sending a GET request to {endpoint}");
+                let response = client.get(&*endpoint).send().await.unwrap_or_else(|e| {
+                    panic!("Should be able to send a GET request ({endpoint})\n\r{e}")
+                });
+                assert_eq!(expected, response.status(), "{role} {path}");
+            }
+        }
+    }
+
+    app.stop().await;
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use reqwest::{header, StatusCode};
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{spawn, spawn_builder, test_builder, test_client};
+use fantasia_web::{
+    app::Role,
+    metrics::{RequestLabels, CONTENT_TYPE},
+};
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn metrics_are_served_on_admin_listeners(pool: PgPool) {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let builder = test_builder(pool).listener(any_port, None, Role::Admin);
+    let metrics = builder.metrics().clone();
+    let app = spawn_builder(builder).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let public = app.role_endpoints(Role::Public, "").pop().unwrap();
+    for path in [
+        "/health/live",
+        "/health/live",
+        "/health/ready",
+        "/no/such/page",
+    ] {
+        client.get(format!("{public}{path}")).send().await.unwrap();
+    }
+
+    // Metrics are private if there's an admin listener
+    let response = client
+        .get(format!("{public}/metrics"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    let endpoint = app.role_endpoints(Role::Admin, "/metrics").pop().unwrap();
+    let response = client.get(&endpoint).send().await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(CONTENT_TYPE, response.headers()[header::CONTENT_TYPE]);
+    let body = response.text().await.unwrap();
+
+    for line in [
+        "# TYPE fantasia_http_requests counter\n",
+        "fantasia_http_requests_total{route=\"/health/live\",method=\"GET\",status=\"200\"} 2\n",
+        // Paths that don't match a route share a label
+        "fantasia_http_requests_total{route=\"unmatched\",method=\"GET\",status=\"404\"} 2\n",
+        "fantasia_http_request_duration_seconds_count{route=\"/health/live\",method=\"GET\",status=\"200\"} 2\n",
+        // Only the scrape itself
+        "fantasia_http_requests_in_flight 1\n",
+        "fantasia_panics_total 0\n",
+        "fantasia_db_pool_max_connections ",
+        // The readiness probe's connection
+        "fantasia_db_pool_acquire_wait_seconds_count 1\n",
+    ] {
+        assert!(body.contains(line), "{line} is missing from:\n{body}");
+    }
+    assert!(body.contains("fantasia_build_info{version=\""), "{body}");
+    assert!(body.ends_with("# EOF\n"), "{body}");
+    if cfg!(target_os = "linux") {
+        assert!(body.contains("\nprocess_resident_memory_bytes "), "{body}");
+    }
+
+    let scrape = RequestLabels {
+        route: "/metrics".to_owned(),
+        method: "GET".to_owned(),
+        status: 200,
+    };
+    assert_eq!(1, metrics.requests.get(&scrape).unwrap().get());
+    assert_eq!(0, metrics.in_flight.get());
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn metrics_are_public_without_admin_listeners(pool: PgPool) {
+    let app = spawn(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    for endpoint in app.endpoints("/metrics") {
+        let response = client.get(&endpoint).send().await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains("# TYPE fantasia_http_request_duration_seconds histogram\n"));
+    }
+
+    app.stop().await;
+}
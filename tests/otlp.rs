@@ -0,0 +1,260 @@
+mod common;
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use reqwest::Url;
+use serde_json::Value;
+use sqlx::PgPool;
+use test_log::test;
+use tokio::net::TcpListener;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use common::{spawn_builder, test_builder, test_client, TestApp};
+use fantasia_web::{
+    app::Role,
+    telemetry::{self, OtlpExporter, OtlpSettings},
+};
+
+const CALLER_TRACE: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const CALLER_SPAN: &str = "00f067aa0ba902b7";
+const OTHER_TRACE: &str = "0af7651916cd43dd8448eb211c80319c";
+
+/// Stand-in for an OpenTelemetry collector's OTLP/HTTP receiver.
+#[derive(Clone, Default)]
+struct Collector(Arc<Mutex<Vec<Value>>>);
+
+impl Collector {
+    async fn spawn() -> (Collector, Url) {
+        let collector = Collector::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+
+        let router = Router::new()
+            .route(
+                "/v1/traces",
+                post(
+                    |State(collector): State<Collector>, body: String| async move {
+                        let body = serde_json::from_str(&body).expect("Exports should be JSON");
+                        collector.0.lock().unwrap().push(body);
+                        StatusCode::OK
+                    },
+                ),
+            )
+            .with_state(collector.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        (collector, Url::parse(&format!("http://{addr}")).unwrap())
+    }
+
+    /// Every exported span with the service name of its resource.
+    fn spans(&self) -> Vec<(String, Value)> {
+        let exports = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        exports
+            .iter()
+            .flat_map(|export| export["resourceSpans"].as_array().unwrap())
+            .flat_map(|resource| {
+                let service = resource["resource"]["attributes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .find(|attribute| attribute["key"] == "service.name")
+                    .and_then(|attribute| attribute["value"]["stringValue"].as_str())
+                    .unwrap_or_default()
+                    .to_owned();
+                resource["scopeSpans"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .flat_map(|scope| scope["spans"].as_array().unwrap())
+                    .map(move |span| (service.clone(), span.clone()))
+            })
+            .collect()
+    }
+
+    /// Request spans of `trace_id`.
+    fn requests(&self, trace_id: &str) -> Vec<Value> {
+        self.spans()
+            .into_iter()
+            .map(|(_, span)| span)
+            .filter(|span| span["name"] == "request" && span["traceId"] == trace_id)
+            .collect()
+    }
+}
+
+// Admin listener with a route that passes the current trace on like an outgoing request would,
+// and routes that log an error or fail
+async fn spawn_traced(pool: PgPool) -> TestApp {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let routes = Router::new()
+        .route(
+            "/outgoing",
+            get(|| async {
+                let mut headers = HeaderMap::new();
+                telemetry::inject(&mut headers);
+                headers
+            }),
+        )
+        .route(
+            "/logs-error",
+            get(|| async {
+                tracing::error!("Handled error");
+                StatusCode::OK
+            }),
+        )
+        .route("/fails", get(|| async { StatusCode::BAD_GATEWAY }));
+
+    spawn_builder(
+        test_builder(pool)
+            .listener(any_port, None, Role::Admin)
+            .admin_routes(routes),
+    )
+    .await
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn requests_continue_the_callers_trace(pool: PgPool) {
+    let (collector, endpoint) = Collector::spawn().await;
+    let mut settings = OtlpSettings::new(endpoint);
+    settings.service_name = "fantasia-test".to_owned();
+    let exporter = OtlpExporter::new(&settings).unwrap();
+    let _guard = tracing_subscriber::registry()
+        .with(exporter.layer())
+        .set_default();
+
+    let app = spawn_traced(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let endpoint = app.role_endpoints(Role::Admin, "/outgoing").pop().unwrap();
+
+    let response = client
+        .get(&endpoint)
+        .header("traceparent", format!("00-{CALLER_TRACE}-{CALLER_SPAN}-01"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, response.status());
+    let outgoing = response.headers()["traceparent"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    app.stop().await;
+    exporter.shutdown().await;
+
+    let requests = collector.requests(CALLER_TRACE);
+    assert_eq!(1, requests.len(), "{:#?}", collector.spans());
+    let request = &requests[0];
+    assert_eq!(CALLER_SPAN, request["parentSpanId"]);
+    // SPAN_KIND_SERVER
+    assert_eq!(2, request["kind"]);
+    assert!(request["attributes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(
+            |attribute| attribute["key"] == "method" && attribute["value"]["stringValue"] == "GET"
+        ));
+
+    // Outgoing requests are children of the request span
+    let span_id = request["spanId"].as_str().unwrap();
+    assert_eq!(format!("00-{CALLER_TRACE}-{span_id}-01"), outgoing);
+
+    assert!(collector
+        .spans()
+        .iter()
+        .all(|(service, _)| service == "fantasia-test"));
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn unsampled_traces_are_not_exported(pool: PgPool) {
+    let (collector, endpoint) = Collector::spawn().await;
+    let exporter = OtlpExporter::new(&OtlpSettings::new(endpoint)).unwrap();
+    let _guard = tracing_subscriber::registry()
+        .with(exporter.layer())
+        .set_default();
+
+    let app = spawn_traced(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let endpoint = app.role_endpoints(Role::Admin, "/outgoing").pop().unwrap();
+
+    let response = client
+        .get(&endpoint)
+        .header("traceparent", format!("00-{CALLER_TRACE}-{CALLER_SPAN}-00"))
+        .send()
+        .await
+        .unwrap();
+    let outgoing = response.headers()["traceparent"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    // The caller's decision is passed on
+    assert!(
+        outgoing.starts_with(&format!("00-{CALLER_TRACE}-")),
+        "{outgoing}"
+    );
+    assert!(outgoing.ends_with("-00"), "{outgoing}");
+
+    // Requests without a caller start their own traces
+    let response = client.get(&endpoint).send().await.unwrap();
+    let own = response.headers()["traceparent"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(!own.contains(CALLER_TRACE), "{own}");
+
+    app.stop().await;
+    exporter.shutdown().await;
+
+    assert!(collector.requests(CALLER_TRACE).is_empty());
+    let own_trace = own.split('-').nth(1).unwrap();
+    let requests = collector.requests(own_trace);
+    assert_eq!(1, requests.len(), "{:#?}", collector.spans());
+    assert_eq!("", requests[0]["parentSpanId"]);
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn only_server_errors_fail_request_spans(pool: PgPool) {
+    let (collector, endpoint) = Collector::spawn().await;
+    let exporter = OtlpExporter::new(&OtlpSettings::new(endpoint)).unwrap();
+    let _guard = tracing_subscriber::registry()
+        .with(exporter.layer())
+        .set_default();
+
+    let app = spawn_traced(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let mut traces = Vec::new();
+    for (path, trace_id) in [("/logs-error", CALLER_TRACE), ("/fails", OTHER_TRACE)] {
+        let endpoint = app.role_endpoints(Role::Admin, path).pop().unwrap();
+        client
+            .get(&endpoint)
+            .header("traceparent", format!("00-{trace_id}-{CALLER_SPAN}-01"))
+            .send()
+            .await
+            .unwrap();
+        traces.push(trace_id);
+    }
+
+    app.stop().await;
+    exporter.shutdown().await;
+
+    // STATUS_CODE_UNSET and STATUS_CODE_ERROR
+    for (trace_id, code) in traces.into_iter().zip([0, 2]) {
+        let requests = collector.requests(trace_id);
+        assert_eq!(1, requests.len(), "{:#?}", collector.spans());
+        assert_eq!(code, requests[0]["status"]["code"], "{:#?}", requests[0]);
+    }
+}
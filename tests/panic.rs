@@ -0,0 +1,68 @@
+mod common;
+
+use axum::{routing::get, Router};
+use reqwest::{header, StatusCode};
+use serde_json::Value;
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{spawn_builder, test_builder, test_client, CapturedLogs};
+use fantasia_web::app::{panic, Role};
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn panics_are_problems_with_the_request_id(pool: PgPool) {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let routes = Router::new().route(
+        "/panic",
+        get(|| async {
+            if true {
+                panic!("Handler gave up on {}", "purpose");
+            }
+        }),
+    );
+
+    // Read once by the first capture
+    std::env::set_var("RUST_LIB_BACKTRACE", "1");
+    panic::install_hook();
+
+    let (logs, _guard) = CapturedLogs::start();
+    let builder = test_builder(pool)
+        .listener(any_port, None, Role::Admin)
+        .admin_routes(routes);
+    let metrics = builder.metrics().clone();
+    let app = spawn_builder(builder).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let endpoint = app.role_endpoints(Role::Admin, "/panic").pop().unwrap();
+
+    let response = client.get(&endpoint).send().await.unwrap();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+    assert_eq!(
+        "application/problem+json",
+        response.headers()[header::CONTENT_TYPE]
+    );
+    let request_id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let body: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(request_id, body["request_id"]);
+    assert!(!body.to_string().contains("purpose"), "{body}");
+
+    // The server keeps serving after a panic
+    let response = client.get(&endpoint).send().await.unwrap();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+    assert_eq!(2, metrics.panics.get());
+
+    app.stop().await;
+
+    let logs = logs.contents();
+    let panicked = logs
+        .lines()
+        .find(|line| line.contains("Handler panicked: Handler gave up on purpose"))
+        .unwrap_or_else(|| panic!("The panic should be logged:\n{logs}"));
+    assert!(panicked.contains(&request_id), "{panicked}");
+    assert!(panicked.contains("backtrace="), "{panicked}");
+}
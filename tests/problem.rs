@@ -0,0 +1,158 @@
+mod common;
+
+use axum::{routing::get, Router};
+use reqwest::{header, StatusCode};
+use serde_json::Value;
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{spawn, spawn_builder, test_builder, test_client};
+use fantasia_web::{app::Role, error::AppError};
+
+async fn problem(response: reqwest::Response) -> (StatusCode, String, Value) {
+    let status = response.status();
+    assert_eq!(
+        "application/problem+json",
+        response.headers()[header::CONTENT_TYPE]
+    );
+    let request_id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let body = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+
+    (status, request_id, body)
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn not_found_is_a_problem_with_the_request_id(pool: PgPool) {
+    let app = spawn(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let response = client
+        .get(app.endpoints("/does_not_exist").pop().unwrap())
+        .send()
+        .await
+        .unwrap();
+    let (status, request_id, body) = problem(response).await;
+
+    assert_eq!(StatusCode::NOT_FOUND, status);
+    assert_eq!("about:blank", body["type"]);
+    assert_eq!("Not Found", body["title"]);
+    assert_eq!(404, body["status"]);
+    assert_eq!(request_id, body["request_id"]);
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn internal_errors_are_not_leaked(pool: PgPool) {
+    let any_port = "127.0.0.1:0".parse().unwrap();
+    let routes = Router::new().route(
+        "/fail",
+        get(|| async {
+            Err::<(), _>(AppError::Database(sqlx::Error::Protocol(
+                "password authentication failed for user \"fantasia\"".into(),
+            )))
+        }),
+    );
+    let app = spawn_builder(
+        test_builder(pool)
+            .listener(any_port, None, Role::Admin)
+            .admin_routes(routes),
+    )
+    .await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let response = client
+        .get(app.role_endpoints(Role::Admin, "/fail").pop().unwrap())
+        .send()
+        .await
+        .unwrap();
+    let (status, request_id, body) = problem(response).await;
+
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status);
+    assert_eq!(request_id, body["request_id"]);
+    assert!(body.get("detail").is_none());
+    assert!(!body.to_string().contains("password"), "{body}");
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn not_found_is_negotiated(pool: PgPool) {
+    let app = spawn(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let endpoint = app.endpoints("/does_not_exist").pop().unwrap();
+
+    for (accept, content_type, expected) in [
+        (
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            "text/html; charset=utf-8",
+            "<h1>404 Not Found</h1>",
+        ),
+        ("text/plain", "text/plain; charset=utf-8", "404 Not Found\n"),
+        (
+            "application/json",
+            "application/problem+json",
+            r#""status":404"#,
+        ),
+    ] {
+        let response = client
+            .get(&endpoint)
+            .header(header::ACCEPT, accept)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+        assert_eq!(content_type, response.headers()[header::CONTENT_TYPE]);
+
+        let request_id = response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let body = response.text().await.unwrap();
+        assert!(body.contains(expected), "{accept}: {body}");
+        assert!(body.contains(&request_id), "{accept}: {body}");
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn method_not_allowed_lists_allowed_methods(pool: PgPool) {
+    let app = spawn(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let response = client
+        .delete(app.endpoints("/health_check").pop().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!("GET,HEAD", response.headers()[header::ALLOW]);
+    let (status, request_id, body) = problem(response).await;
+
+    assert_eq!(StatusCode::METHOD_NOT_ALLOWED, status);
+    assert_eq!("Method Not Allowed", body["title"]);
+    assert_eq!(request_id, body["request_id"]);
+
+    let response = client
+        .post(app.endpoints("/").pop().unwrap())
+        .header(header::ACCEPT, "text/plain")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::METHOD_NOT_ALLOWED, response.status());
+    assert_eq!("GET,HEAD", response.headers()[header::ALLOW]);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .starts_with("405 Method Not Allowed\n"));
+
+    app.stop().await;
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use sqlx::PgPool;
+use test_log::test;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use common::{spawn_builder, test_builder};
+use fantasia_web::app::ListenAddr;
+
+const REQUEST: &[u8] =
+    b"GET /health_check HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+// Send `header` followed by a request and return the raw response
+async fn send(addr: &ListenAddr, header: &[u8]) -> String {
+    let ListenAddr::Tcp(addr) = addr else {
+        panic!("Expected a TCP listener");
+    };
+
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("Should be able to connect to the listener");
+    stream
+        .write_all(&[header, REQUEST].concat())
+        .await
+        .expect("Should be able to send a GET request");
+
+    let mut response = String::new();
+    // The server may reset the connection before the request is read
+    let _ = stream.read_to_string(&mut response).await;
+    response
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn proxy_headers_are_accepted(pool: PgPool) {
+    let app = spawn_builder(test_builder(pool).proxy_protocol(true)).await;
+    let (addr, ..) = &app.listeners[0];
+
+    let v1 = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n";
+    let mut v2 = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c".to_vec();
+    v2.extend([192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb]);
+
+    for header in [&v1[..], &v2] {
+        let response = send(addr, header).await;
+        assert!(
+            response.starts_with("HTTP/1.1 200 OK"),
+            "Unexpected response: {response}"
+        );
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn missing_proxy_header_is_rejected(pool: PgPool) {
+    let app = spawn_builder(test_builder(pool).proxy_protocol(true)).await;
+    let (addr, ..) = &app.listeners[0];
+
+    for header in [&b""[..], b"PROXY TCP4 192.0.2.1\r\n"] {
+        let response = send(addr, header).await;
+        assert!(response.is_empty(), "Unexpected response: {response}");
+    }
+
+    app.stop().await;
+}
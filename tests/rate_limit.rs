@@ -0,0 +1,104 @@
+mod common;
+
+use std::num::NonZeroU32;
+
+use reqwest::{header, StatusCode};
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{spawn_builder, test_builder, test_client};
+use fantasia_web::app::{
+    rate_limit::{Limit, RateLimitSettings, RouteLimit},
+    HttpSettings,
+};
+use serde_json::Value;
+
+fn limit(burst: u32, requests_per_minute: u32) -> Limit {
+    Limit {
+        burst: NonZeroU32::new(burst).unwrap(),
+        requests_per_minute: NonZeroU32::new(requests_per_minute).unwrap(),
+    }
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn clients_are_limited_per_route_group(pool: PgPool) {
+    let http = HttpSettings {
+        rate_limit: Some(RateLimitSettings {
+            limit: limit(2, 1),
+            routes: vec![RouteLimit {
+                prefix: "/health_check".into(),
+                limit: limit(1, 1),
+            }],
+        }),
+        ..Default::default()
+    };
+    let app = spawn_builder(test_builder(pool).http(http)).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let index = app.endpoints("/").pop().unwrap();
+    let health_check = app.endpoints("/health_check").pop().unwrap();
+
+    for remaining in ["1", "0"] {
+        let response = client.get(&index).send().await.unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("2", response.headers()["ratelimit-limit"]);
+        assert_eq!(remaining, response.headers()["ratelimit-remaining"]);
+    }
+
+    let response = client.get(&index).send().await.unwrap();
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, response.status());
+    assert_eq!("0", response.headers()["ratelimit-remaining"]);
+    assert_eq!("60", response.headers()[header::RETRY_AFTER]);
+    assert_eq!("120", response.headers()["ratelimit-reset"]);
+    assert_eq!(
+        "application/problem+json",
+        response.headers()[header::CONTENT_TYPE]
+    );
+    let problem: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(429, problem["status"]);
+    assert!(problem["request_id"].is_string());
+
+    // `/health_check` has its own bucket
+    let response = client.get(&health_check).send().await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("1", response.headers()["ratelimit-limit"]);
+
+    let response = client.get(&health_check).send().await.unwrap();
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, response.status());
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn reloaded_limits_apply_to_new_requests(pool: PgPool) {
+    let http = HttpSettings {
+        rate_limit: Some(RateLimitSettings {
+            limit: limit(1, 1),
+            routes: Vec::new(),
+        }),
+        ..Default::default()
+    };
+    let builder = test_builder(pool).http(http);
+    let limits = builder.limits().clone();
+    let app = spawn_builder(builder).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let index = app.endpoints("/").pop().unwrap();
+
+    assert_eq!(
+        StatusCode::OK,
+        client.get(&index).send().await.unwrap().status()
+    );
+    assert_eq!(
+        StatusCode::TOO_MANY_REQUESTS,
+        client.get(&index).send().await.unwrap().status()
+    );
+
+    limits.reload(&HttpSettings::default());
+    let response = client.get(&index).send().await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(response.headers().get("ratelimit-limit").is_none());
+
+    app.stop().await;
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use axum::{http::header, routing::get, Router};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{spawn_builder, test_builder, test_client, CapturedLogs};
+use fantasia_web::app::{trace::RedactedHeaders, HttpSettings, Role};
+
+// Sends secrets in request and response headers and returns the captured logs
+async fn logged_request(pool: PgPool, http: HttpSettings) -> String {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let login = Router::new().route(
+        "/login",
+        get(|| async { [(header::SET_COOKIE, "session=hunter3")] }),
+    );
+
+    let (logs, _guard) = CapturedLogs::start();
+    let app = spawn_builder(
+        test_builder(pool)
+            .listener(any_port, None, Role::Admin)
+            .admin_routes(login)
+            .http(http),
+    )
+    .await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    for endpoint in app.role_endpoints(Role::Admin, "/login") {
+        let response = client
+            .get(&endpoint)
+            .header(reqwest::header::AUTHORIZATION, "Bearer hunter2")
+            .header("x-fantasia-visible", "shown")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    app.stop().await;
+    logs.contents()
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn secret_headers_are_redacted(pool: PgPool) {
+    let logs = logged_request(pool, HttpSettings::default()).await;
+
+    assert!(logs.contains("finished processing request"), "{logs}");
+    assert!(logs.contains("shown"), "Other headers should be logged");
+    assert!(logs.contains("[redacted]"), "{logs}");
+    assert!(!logs.contains("hunter2"), "Authorization leaked:\n{logs}");
+    assert!(!logs.contains("hunter3"), "Set-Cookie leaked:\n{logs}");
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn redacted_headers_are_configurable(pool: PgPool) {
+    let http = HttpSettings {
+        redact_headers: RedactedHeaders::try_from(vec!["X-Fantasia-Visible".to_owned()])
+            .expect("`X-Fantasia-Visible` is a valid header name"),
+        ..Default::default()
+    };
+    let logs = logged_request(pool, http).await;
+
+    assert!(logs.contains("finished processing request"), "{logs}");
+    assert!(!logs.contains("shown"), "Configured header leaked:\n{logs}");
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use axum::{routing::get, Router};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{spawn_builder, test_builder, test_client, CapturedLogs, TestApp};
+use fantasia_web::{app::Role, extract::RequestId};
+
+// Admin listener with a route that logs and returns its request ID
+async fn spawn_echo(pool: PgPool) -> TestApp {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let routes = Router::new().route(
+        "/request_id",
+        get(|request_id: RequestId| async move {
+            tracing::info!("Handling the request");
+            request_id.to_string()
+        }),
+    );
+
+    spawn_builder(
+        test_builder(pool)
+            .listener(any_port, None, Role::Admin)
+            .admin_routes(routes),
+    )
+    .await
+}
+
+async fn send(app: &TestApp, request_id: Option<&str>) -> (String, String) {
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let mut request = client.get(
+        app.role_endpoints(Role::Admin, "/request_id")
+            .pop()
+            .unwrap(),
+    );
+    if let Some(request_id) = request_id {
+        request = request.header("x-request-id", request_id);
+    }
+
+    let response = request.send().await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let header = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    (header, response.text().await.unwrap())
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn valid_client_ids_are_honored(pool: PgPool) {
+    let (logs, _guard) = CapturedLogs::start();
+    let app = spawn_echo(pool).await;
+
+    let (header, body) = send(&app, Some("req-2f6e.client")).await;
+    assert_eq!("req-2f6e.client", header);
+    assert_eq!(header, body, "Handlers should see the same ID");
+
+    app.stop().await;
+
+    // The root span's ID applies to events from handlers
+    let logs = logs.contents();
+    let handled = logs
+        .lines()
+        .find(|line| line.contains("Handling the request"))
+        .expect("The handler should log");
+    assert!(handled.contains("request_id=req-2f6e.client"), "{logs}");
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn invalid_client_ids_are_replaced(pool: PgPool) {
+    let app = spawn_echo(pool).await;
+
+    let (generated, body) = send(&app, None).await;
+    assert_eq!(36, generated.len(), "Generated IDs are UUIDs");
+    assert_eq!(generated, body);
+
+    for invalid in ["has spaces", &"a".repeat(129)] {
+        let (header, body) = send(&app, Some(invalid)).await;
+        assert_ne!(invalid, header);
+        assert_eq!(36, header.len());
+        assert_eq!(header, body);
+    }
+
+    app.stop().await;
+}
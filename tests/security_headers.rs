@@ -0,0 +1,94 @@
+mod common;
+
+use axum::http::HeaderValue;
+use reqwest::{header, StatusCode};
+use sqlx::PgPool;
+use test_log::test;
+
+use common::{
+    spawn, spawn_builder, spawn_tls, test_builder, test_client, test_tls_client, TestApp, TestCert,
+};
+use fantasia_web::app::{security_headers::SecurityHeaders, HttpSettings};
+
+async fn headers(client: &reqwest::Client, app: &TestApp, path: &str) -> header::HeaderMap {
+    client
+        .get(app.endpoints(path).pop().unwrap())
+        .send()
+        .await
+        .unwrap()
+        .headers()
+        .clone()
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn defaults_apply_to_routes_and_the_fallback(pool: PgPool) {
+    let app = spawn(pool).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let response = client
+        .get(app.endpoints("/does_not_exist").pop().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    for headers in [
+        headers(&client, &app, "/").await,
+        response.headers().clone(),
+    ] {
+        assert_eq!("nosniff", headers[header::X_CONTENT_TYPE_OPTIONS]);
+        assert_eq!("DENY", headers[header::X_FRAME_OPTIONS]);
+        assert_eq!("no-referrer", headers[header::REFERRER_POLICY]);
+        assert!(headers.contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(headers.contains_key("permissions-policy"));
+        assert!(
+            !headers.contains_key(header::STRICT_TRANSPORT_SECURITY),
+            "HSTS is only sent over TLS"
+        );
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn hsts_is_sent_over_tls(pool: PgPool) {
+    let cert = TestCert::generate();
+    let app = spawn_tls(pool, &cert).await;
+    let client = test_tls_client(&cert, false).expect("Should be able to build an HTTPS client");
+
+    let headers = headers(&client, &app, "/health_check").await;
+    assert_eq!(
+        "max-age=31536000; includeSubDomains",
+        headers[header::STRICT_TRANSPORT_SECURITY]
+    );
+    assert_eq!("nosniff", headers[header::X_CONTENT_TYPE_OPTIONS]);
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn headers_are_overridable(pool: PgPool) {
+    let http = HttpSettings {
+        security_headers: SecurityHeaders {
+            content_security_policy: Some(HeaderValue::from_static("default-src 'self'")),
+            x_frame_options: None,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let app = spawn_builder(test_builder(pool).http(http)).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    let headers = headers(&client, &app, "/").await;
+    assert_eq!(
+        "default-src 'self'",
+        headers[header::CONTENT_SECURITY_POLICY]
+    );
+    assert!(!headers.contains_key(header::X_FRAME_OPTIONS));
+    assert_eq!("nosniff", headers[header::X_CONTENT_TYPE_OPTIONS]);
+
+    app.stop().await;
+}
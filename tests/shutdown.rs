@@ -0,0 +1,93 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{routing::get, Router};
+use sqlx::PgPool;
+use test_log::test;
+use tokio::{sync::Notify, time};
+
+use common::{spawn, spawn_builder, test_builder, test_client};
+use fantasia_web::app::Role;
+
+const SLOW_REQUEST: Duration = Duration::from_secs(2);
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn stopped_servers_refuse_connections(pool: PgPool) {
+    let app = spawn(pool).await;
+    let endpoints = app.endpoints("/health_check");
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    app.stop().await;
+
+    for endpoint in endpoints {
+        let result = client.get(&*endpoint).send().await;
+        assert!(
+            result.is_err(),
+            "Stopped server should not answer requests ({endpoint})"
+        );
+    }
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn in_flight_requests_finish_after_shutdown(pool: PgPool) {
+    let any_port = "127.0.0.1:0"
+        .parse()
+        .expect("`127.0.0.1:0` is a valid address");
+    let started = Arc::new(Notify::new());
+    let routes = Router::new().route(
+        "/slow",
+        get({
+            let started = started.clone();
+            move || async move {
+                started.notify_one();
+                time::sleep(SLOW_REQUEST).await;
+                "Finished"
+            }
+        }),
+    );
+    let app = spawn_builder(
+        test_builder(pool)
+            .listener(any_port, None, Role::Admin)
+            .admin_routes(routes),
+    )
+    .await;
+    let slow = app.role_endpoints(Role::Admin, "/slow").pop().unwrap();
+    let health = app
+        .role_endpoints(Role::Admin, "/health_check")
+        .pop()
+        .unwrap();
+
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let request = tokio::spawn(async move { client.get(&slow).send().await });
+    started.notified().await;
+
+    app.shutdown().trigger();
+
+    // The listener is closed once the accept loop sees the shutdown
+    let new_client = test_client().expect("Should be able to build an HTTP client");
+    let mut refused = false;
+    for _ in 0..20 {
+        if new_client.get(&health).send().await.is_err() {
+            refused = true;
+            break;
+        }
+        time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(refused, "New connections should be refused after shutdown");
+    assert!(
+        !request.is_finished(),
+        "The slow request finished too early"
+    );
+
+    let response = request
+        .await
+        .expect("The request task should not panic")
+        .expect("In-flight requests should finish");
+    assert_eq!(reqwest::StatusCode::OK, response.status());
+    assert_eq!("Finished", response.text().await.unwrap());
+
+    app.stop().await;
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use std::{
+    net::TcpListener,
+    os::fd::{IntoRawFd, OwnedFd},
+};
+
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use test_log::test;
+use tracing::info;
+
+use common::{spawn_builder, test_builder, test_client};
+use fantasia_web::app::{InheritedSocket, ListenAddr, Role};
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn inherited_sockets_serve_requests(pool: PgPool) {
+    // Stand in for systemd by binding the socket and passing its descriptor
+    let listener =
+        TcpListener::bind("127.0.0.1:0").expect("Binding to a local socket should succeed");
+    let addr = listener
+        .local_addr()
+        .expect("Bound socket should have an address");
+    let fd = listener.into_raw_fd();
+
+    // SAFETY: `fd` was just released by `listener`
+    let socket = unsafe { InheritedSocket::from_raw_fd(fd) }
+        .expect("A listening TCP socket should be accepted");
+    let app =
+        spawn_builder(test_builder(pool).inherited_listener(socket, None, Role::Public)).await;
+    assert!(app
+        .listeners
+        .iter()
+        .any(|(sock_addr, _, _)| *sock_addr == ListenAddr::Tcp(addr)));
+
+    let client = test_client().expect("Should be able to build an HTTP client");
+    let endpoint = format!("http://{addr}/health_check");
+    info!("Sending a GET request to {endpoint}");
+    let response = client
+        .get(&*endpoint)
+        .send()
+        .await
+        .unwrap_or_else(|e| panic!("Should be able to send a GET request ({endpoint})\n\r{e}"));
+    assert_eq!(StatusCode::OK, response.status());
+
+    app.stop().await;
+}
+
+#[test]
+fn unbound_sockets_are_rejected() {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").expect("Binding UDP should succeed");
+    let fd = OwnedFd::from(socket).into_raw_fd();
+
+    // SAFETY: `fd` was just released by `socket`
+    let result = unsafe { InheritedSocket::from_raw_fd(fd) };
+    assert!(result.is_err(), "Datagram sockets should be rejected");
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::{fs, time::Duration};
+
+use reqwest::{StatusCode, Version};
+use sqlx::PgPool;
+use test_log::test;
+use tokio::{net::TcpStream, time};
+use tracing::info;
+
+use common::{spawn_builder, spawn_tls, test_builder, test_client, test_tls_client, TestCert};
+use fantasia_web::app::ListenAddr;
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn https_negotiates_http2(pool: PgPool) {
+    let cert = TestCert::generate();
+    let app = spawn_tls(pool, &cert).await;
+    let client = test_tls_client(&cert, false).expect("Should be able to build an HTTPS client");
+
+    for endpoint in app.endpoints("/health_check") {
+        info!("Sending a GET request to {endpoint}");
+        let response =
+            client.get(&*endpoint).send().await.unwrap_or_else(|e| {
+                panic!("Should be able to send a GET request ({endpoint})\n\r{e}")
+            });
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(Version::HTTP_2, response.version());
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn https_falls_back_to_http1(pool: PgPool) {
+    let cert = TestCert::generate();
+    let app = spawn_tls(pool, &cert).await;
+    let client = test_tls_client(&cert, true).expect("Should be able to build an HTTPS client");
+
+    for endpoint in app.endpoints("/health_check") {
+        let response =
+            client.get(&*endpoint).send().await.unwrap_or_else(|e| {
+                panic!("Should be able to send a GET request ({endpoint})\n\r{e}")
+            });
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(Version::HTTP_11, response.version());
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn https_rejects_plain_http(pool: PgPool) {
+    let cert = TestCert::generate();
+    let app = spawn_tls(pool, &cert).await;
+    let client = test_client().expect("Should be able to build an HTTP client");
+
+    for endpoint in app.endpoints("/health_check") {
+        let endpoint = endpoint.replacen("https", "http", 1);
+        assert!(
+            client.get(&*endpoint).send().await.is_err(),
+            "Plain HTTP should fail on a TLS listener ({endpoint})"
+        );
+    }
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn reloaded_certificates_apply_to_new_handshakes(pool: PgPool) {
+    let mut cert = TestCert::generate();
+    let (acceptor, reloadable) = cert
+        .settings
+        .reloadable_acceptor()
+        .expect("Loading a generated certificate should succeed");
+    let app = spawn_builder(test_builder(pool).tls(acceptor)).await;
+    let endpoint = &app.endpoints("/health_check")[0];
+
+    let connected = test_tls_client(&cert, false).expect("Should be able to build an HTTPS client");
+    let response = connected.get(endpoint).send().await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let old_cert = test_tls_client(&cert, false).expect("Should be able to build an HTTPS client");
+    cert.rotate();
+    reloadable
+        .reload()
+        .expect("Reloading a generated certificate should succeed");
+
+    let response = connected.get(endpoint).send().await.unwrap();
+    assert_eq!(
+        StatusCode::OK,
+        response.status(),
+        "Established connections should keep their certificate"
+    );
+    assert!(
+        old_cert.get(endpoint).send().await.is_err(),
+        "New handshakes should use the reloaded certificate"
+    );
+    let new_cert = test_tls_client(&cert, false).expect("Should be able to build an HTTPS client");
+    assert_eq!(
+        StatusCode::OK,
+        new_cert.get(endpoint).send().await.unwrap().status()
+    );
+
+    fs::write(&cert.settings.key, "not a key").unwrap();
+    assert!(
+        reloadable.reload().is_err(),
+        "A broken key should be rejected"
+    );
+    let still_new = test_tls_client(&cert, false).expect("Should be able to build an HTTPS client");
+    assert_eq!(
+        StatusCode::OK,
+        still_new.get(endpoint).send().await.unwrap().status(),
+        "The previous certificate should be kept"
+    );
+
+    app.stop().await;
+}
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn stalled_handshakes_do_not_delay_shutdown(pool: PgPool) {
+    let cert = TestCert::generate();
+    let app = spawn_tls(pool, &cert).await;
+
+    // Connect without ever sending a ClientHello
+    let mut stalled = Vec::new();
+    for (addr, _, _) in &app.listeners {
+        if let ListenAddr::Tcp(addr) = addr {
+            stalled.push(
+                TcpStream::connect(addr)
+                    .await
+                    .expect("Should be able to connect"),
+            );
+        }
+    }
+    assert!(!stalled.is_empty());
+
+    // Well within the grace period
+    time::timeout(Duration::from_secs(2), app.stop())
+        .await
+        .expect("Shutdown should not wait on handshakes");
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use sqlx::PgPool;
+use test_log::test;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+use tracing::info;
+
+use common::{spawn_builder, test_builder};
+use fantasia_web::app::{Role, UnixSocket};
+
+#[tracing::instrument(skip(pool))]
+#[test(sqlx::test)]
+async fn unix_socket_serves_requests(pool: PgPool) {
+    let dir = tempfile::tempdir().expect("Creating a temporary directory should succeed");
+    let path = dir.path().join("fantasia.sock");
+    let socket = UnixSocket {
+        path: path.clone(),
+        mode: Some(0o600),
+        uid: None,
+        gid: None,
+    };
+
+    let app = spawn_builder(test_builder(pool).unix_listener(socket, None, Role::Public)).await;
+
+    let mode = fs::metadata(&path)
+        .expect("Unix socket should exist while serving")
+        .permissions()
+        .mode();
+    assert_eq!(0o600, mode & 0o777);
+    // The socket was bound elsewhere and moved into place with its mode already set
+    let entries: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(vec![path.clone()], entries);
+
+    info!("Sending a GET request to {}", path.display());
+    let mut stream = UnixStream::connect(&path)
+        .await
+        .expect("Should be able to connect to the Unix socket");
+    stream
+        .write_all(b"GET /health_check HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .expect("Should be able to send a GET request");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("Should be able to read the response");
+    assert!(
+        response.starts_with("HTTP/1.1 200 OK"),
+        "Unexpected response: {response}"
+    );
+
+    app.stop().await;
+    assert!(!path.exists(), "Unix socket should be removed on shutdown");
+}